@@ -1,12 +1,13 @@
 use adventofcode::read_input_file;
+use std::io;
 
 
-fn collect_input(file : &str) -> Vec<i32> {
-    read_input_file(file)
+fn collect_input(file : &str) -> io::Result<Vec<i32>> {
+    Ok(read_input_file(file)?
         .split("\n")
         .map( |x| x.parse::<i32>())
         .filter_map(|x| x.ok())
-        .collect()
+        .collect())
 }
 
 fn calculate_fuel_cost( mass : i32 ) -> i32 {
@@ -20,23 +21,23 @@ fn calculate_fuel_cost_rec(mass : i32) -> i32{
         return cost + calculate_fuel_cost_rec(cost)
     }
 
-    return 0
+    0
 }
 
 fn sum_fuel_cost_for_modules( modules : Vec<i32>, fuel_calculator : fn(i32) -> i32) -> i32 {
     modules.into_iter().map(fuel_calculator).sum()
 }
 
-fn main(){
+fn main() -> io::Result<()>{
     let part_1_fuel_cost =
         sum_fuel_cost_for_modules(
-            collect_input("day-1-part-1-input"),
+            collect_input("day-1-part-1-input")?,
             calculate_fuel_cost
         );
 
     let part_2_fuel_cost =
         sum_fuel_cost_for_modules(
-            collect_input("day-1-part-2-input"),
+            collect_input("day-1-part-2-input")?,
             calculate_fuel_cost_rec
         );
 
@@ -45,7 +46,9 @@ fn main(){
         "Part 1 {}\nPart 2 {}",
         part_1_fuel_cost,
         part_2_fuel_cost
-    )
+    );
+
+    Ok(())
 }
 
 #[cfg(test)]
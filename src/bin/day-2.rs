@@ -1,12 +1,13 @@
-use adventofcode::read_input_file;
-use std::collections::HashMap;
+use adventofcode::resource_path;
+use adventofcode::int_code_computer::loader::{load_path, LoadError};
+use adventofcode::int_code_computer::solver::{Solver, Target, Variable};
 
 #[derive(PartialEq, Copy, Clone, Debug)]
 enum Opcode {
     Add,
     Mult,
     ProgramEnd,
-    InvalidOpcode
+    Invalid
 }
 
 impl Opcode {
@@ -18,20 +19,20 @@ impl Opcode {
         }
     }
 
+    #[allow(dead_code)]
     fn disassemble(&self, args : Option<&[i32]>) -> String {
         match * self {
             Opcode::Add => format!("ADD &{},&{},&{}", args.unwrap()[0], args.unwrap()[1], args.unwrap()[2]),
             Opcode::Mult => format!("MULT &{},&{},&{}", args.unwrap()[0], args.unwrap()[1], args.unwrap()[2]),
-            Opcode::ProgramEnd => format!("HALT"),
-            Opcode::InvalidOpcode => format!("INVALID_OPCODE @{}:{}", args.unwrap()[0], args.unwrap()[1])
+            Opcode::ProgramEnd => "HALT".to_string(),
+            Opcode::Invalid => format!("INVALID_OPCODE @{}:{}", args.unwrap()[0], args.unwrap()[1])
         }
     }
 
-    fn introspect(&self, program_counter: usize, memory : &Vec<i32>) -> String{
-        let self_size = self.get_size();
-
+    #[allow(dead_code)]
+    fn introspect(&self, program_counter: usize, memory : &[i32]) -> String{
         match * self {
-            Opcode::ProgramEnd|Opcode::InvalidOpcode => format!("()"),
+            Opcode::ProgramEnd|Opcode::Invalid => "()".to_string(),
             Opcode::Add => {
                 let arg1 = memory[memory[program_counter +1] as usize];
                 let arg2 = memory[memory[program_counter +2] as usize];
@@ -54,7 +55,7 @@ fn get_opcode(opcode : &i32) -> Opcode {
         1 => Opcode::Add,
         2 => Opcode::Mult,
         99 => Opcode::ProgramEnd,
-        _ => Opcode::InvalidOpcode
+        _ => Opcode::Invalid
     }
 }
 
@@ -67,7 +68,7 @@ fn get_opcode_result(opcode : Opcode, args : Option<&[i32]> ) -> i32 {
 }
 
 
-fn run_opcode(opcode: Opcode, program_counter: usize, memory : &mut Vec<i32>){
+fn run_opcode(opcode: Opcode, program_counter: usize, memory : &mut [i32]){
     let op_size = opcode.get_size();
     let args : Vec<i32> = memory[program_counter +1..program_counter +op_size-1].iter()
         .map(|x| memory[*x as usize])
@@ -89,7 +90,7 @@ fn run_opcode(opcode: Opcode, program_counter: usize, memory : &mut Vec<i32>){
     }
 }
 
-fn run_program(program : &mut Vec<i32>){
+fn run_program(program : &mut [i32]){
     let mut program_counter = 0;
 
     while program_counter < program.len(){
@@ -100,8 +101,8 @@ fn run_program(program : &mut Vec<i32>){
             Opcode::ProgramEnd => {
                 return;
             },
-            Opcode::InvalidOpcode => {
-                panic!(format!("Received invalid opcode {}", el))
+            Opcode::Invalid => {
+                panic!("Received invalid opcode {}", el)
             }
             _ => {
                 let step = opcode.get_size();
@@ -113,9 +114,10 @@ fn run_program(program : &mut Vec<i32>){
     }
 }
 
-fn disassemble(program : &mut Vec<i32>) -> String {
+#[allow(dead_code)]
+fn disassemble(program : &mut [i32]) -> String {
     let mut program_counter = 0;
-    let mut assembly= format!("");
+    let mut assembly= String::new();
 
     while program_counter < program.len(){
         let el = &program[program_counter];
@@ -123,7 +125,7 @@ fn disassemble(program : &mut Vec<i32>) -> String {
         let size = opcode.get_size();
 
         match opcode {
-            Opcode::InvalidOpcode => {
+            Opcode::Invalid => {
                 assembly = format!("{}{}", assembly, opcode.disassemble(Some(&[program_counter as i32, *el])));
                 return assembly;
             }
@@ -133,14 +135,14 @@ fn disassemble(program : &mut Vec<i32>) -> String {
             }
             _ => {
                 let op_size = opcode.get_size();
-                let args : Vec<i32> = program[program_counter +1..program_counter +op_size].iter().map(|x| *x).collect();
+                let args : Vec<i32> = program[program_counter +1..program_counter +op_size].to_vec();
 
                 assembly = format!(
                     "{}{}:{}={}",
                     assembly,
                     program_counter,
                     opcode.disassemble(Some(&args)),
-                    opcode.introspect(program_counter, &program)
+                    opcode.introspect(program_counter, program)
                 );
 
                 run_opcode(opcode, program_counter, program);
@@ -154,156 +156,40 @@ fn disassemble(program : &mut Vec<i32>) -> String {
     assembly
 }
 
-/// Resolve an Address in memory
-fn resolve(address: i32, address_resolutions : &HashMap<i32, (Opcode, i32, i32)>, program : &Vec<i32>) -> i32 {
-    if address_resolutions.contains_key(&address){
-        let (opcode, lhs, rhs) = address_resolutions.get(&address).unwrap();
-        let resolved_lhs = resolve(*lhs, &address_resolutions, &program);
-        let resolved_rhs = resolve(*rhs, &address_resolutions, &program);
-
-        return get_opcode_result(
-            *opcode,
-            Some(&[resolved_lhs, resolved_rhs])
-        );
-    } else if address > 0 && (address as usize) < program.len() {
-        return program[address as usize]
-    }
-
-    return 0;
-}
-
-fn derive(
-    value :i32,
-    result : i32,
-    address : i32,
-    address_resolutions: &HashMap<i32, (Opcode, i32, i32)>,
-    program : &Vec<i32>,
-) -> Option<(i32, bool)>{
-    if address_resolutions.contains_key(&address){
-        let (opcode, lhs, rhs) = address_resolutions.get(&address).unwrap();
-
-        if *lhs == value && *rhs == value{
-            return None
-        } else if *lhs == value{
-            match(opcode){
-                Opcode::Add => {
-                    return Some((result - resolve(*rhs, &address_resolutions, &program), true));
-                }
-                Opcode::Mult => {
-                    return Some((result / resolve(*rhs, &address_resolutions, &program), true));
-                }
-                _=>panic!()
-            }
-
-        } else if *rhs == value{
-            match(opcode){
-                Opcode::Add => {
-                    return Some((result - resolve(*lhs, &address_resolutions, &program), true));
-                }
-                Opcode::Mult => {
-                    return Some((result / resolve(*lhs, &address_resolutions, &program), true));
-                }
-                _=>panic!()
-            }
-        } else{
-            let next_lhs_result = match opcode {
-                Opcode::Add => result-resolve(*rhs, &address_resolutions, &program),
-                Opcode::Mult=> result/resolve(*rhs, &address_resolutions, &program),
-                _ => panic!()
-            };
-
-            let next_rhs_result = match opcode {
-                Opcode::Add => result-resolve(*lhs, &address_resolutions, &program),
-                Opcode::Mult=> result/resolve(*lhs, &address_resolutions, &program),
-                _ => panic!()
-            };
-
-            let derived_lhs = derive(value, next_lhs_result, *lhs, &address_resolutions, &program);
-            let derived_rhs = derive(value, next_rhs_result, *rhs, &address_resolutions, &program);
-
-            if let Some((result, found)) = derived_lhs{
-                if found {
-                    return derived_lhs;
-                }
-            }
-
-            if let Some((result, found)) = derived_rhs{
-                if found {
-                    return derived_rhs;
-                }
-            }
-
-            return None;
-        }
-    }
-
-    return Some((program[address as usize], false))
-}
-
-fn collect_input(file : &str) -> Vec<i32> {
-    read_input_file(file)
-        .split(",")
-        .map( |x| x.parse::<i32>())
-        .filter_map(|x| x.ok())
-        .collect()
+fn collect_input(file : &str) -> Result<Vec<i32>, LoadError> {
+    load_path(resource_path(file))
 }
 
-fn run_program_1(){
-    let mut program_part_one = collect_input("day-2-part-1-input");
+fn run_program_1() -> Result<(), LoadError>{
+    let mut program_part_one = collect_input("day-2-part-1-input")?;
 
     program_part_one[1] = 12;
     program_part_one[2] = 2;
 
     run_program(&mut program_part_one);
-    println!("Program 1 Result: {}", program_part_one[0])
+    println!("Program 1 Result: {}", program_part_one[0]);
+
+    Ok(())
 }
 
-fn run_program_2(){
+fn run_program_2() -> Result<(), LoadError>{
     let program_result = 19690720;
-    let mut program = collect_input("day-2-part-2-input");
+    let mut program = collect_input("day-2-part-2-input")?;
+    let int_program : Vec<i64> = program.iter().map(|x| *x as i64).collect();
+    let mut solver = Solver::new(&int_program, Target::Cell { address : 0, value : program_result as i64 });
 
-    program[1] = 0;
-    program[2] = 0;
+    solver.add_variable(Variable::Cell(1), 0..=99);
+    solver.add_variable(Variable::Cell(2), 0..=99);
 
-    let mut address_resolutions : HashMap<i32, (Opcode, i32, i32)> = HashMap::new();
-    let mut program_counter: usize = 0;
-
-    loop{
-        let instruction = program[program_counter];
-        let opcode = get_opcode(&instruction);
-
-        let step = match opcode{
-            Opcode::Add => opcode.get_size(),
-            Opcode::Mult => opcode.get_size(),
-            Opcode::ProgramEnd => 0,
-            Opcode::InvalidOpcode => panic!("")
-        };
-
-        if step == 0 {
-            break;
+    match solver.solve() {
+        Some(solution) => {
+            program[1] = solution.assignment[&Variable::Cell(1)] as i32;
+            program[2] = solution.assignment[&Variable::Cell(2)] as i32;
+        },
+        None => {
+            println!("Couldn't find a noun and verb");
+            return Ok(());
         }
-
-        let arg1 = program[program_counter +1];
-        let arg2 = program[program_counter +2];
-        let indexer = program[program_counter +3];
-
-        address_resolutions.insert(indexer, (opcode, arg1, arg2));
-        program_counter += step;
-    }
-
-    if let Some((noun,_)) = derive(1, program_result, 0, &address_resolutions, &program){
-        program[1] = noun;
-
-    }else{
-        println!("Couldn't derive a noun");
-        return
-    }
-
-    if let Some((verb,_)) = derive(2, program_result, 0, &address_resolutions, &program){
-        program[2] = verb;
-    } else {
-        println!("Couldn't derive a verb");
-        return;
     }
 
     println!("Noun: {}, Verb: {}", program[1], program[2]);
@@ -311,21 +197,23 @@ fn run_program_2(){
     println!("Program 2 Result: {}", program[0]);
     println!("Answer: {}", 100* program[1]+program[2]);
     assert_eq!(program[0], program_result);
+
+    Ok(())
 }
 
-fn main(){
-    run_program_1();
-    run_program_2();
+fn main() -> Result<(), LoadError>{
+    run_program_1()?;
+    run_program_2()
 }
 
 #[cfg(test)]
 mod day_2_tests{
-    use crate::{get_opcode, Opcode, run_program, run_opcode, disassemble};
+    use crate::{get_opcode, Opcode, run_program};
 
     #[test]
     fn process_add_opcode(){
         match  get_opcode(&1){
-           Opcode::Add => assert!(true),
+           Opcode::Add => {},
             _ => panic!()
         }
     }
@@ -333,7 +221,7 @@ mod day_2_tests{
     #[test]
     fn process_mult_opcode(){
         match  get_opcode(&2){
-            Opcode::Mult => assert!(true),
+            Opcode::Mult => {},
             _ => panic!()
         }
     }
@@ -341,7 +229,7 @@ mod day_2_tests{
     #[test]
     fn process_program_end_opcode(){
         match get_opcode(&99) {
-            Opcode::ProgramEnd => assert!(true),
+            Opcode::ProgramEnd => {},
             _ => panic!()
         }
     }
@@ -349,7 +237,7 @@ mod day_2_tests{
     #[test]
     fn process_invalid_opcode(){
         match get_opcode(&123) {
-            Opcode::InvalidOpcode => assert!(true),
+            Opcode::Invalid => {},
             _ => panic!()
         }
     }
@@ -1,4 +1,5 @@
 use adventofcode::read_input_file;
+use std::io;
 
 type DirectionCoord = (char, f32);
 
@@ -23,12 +24,12 @@ impl Coord {
 
     fn closest_to(&self, haystack : &Vec<Coord>) -> (Option<Coord>, f32) {
         let mut closest_point = None;
-        let mut closest_distance = std::f32::INFINITY;
+        let mut closest_distance = f32::INFINITY;
 
         for candidate in haystack {
-            let next_dist = Coord::manhatten_dist(&self, candidate);
+            let next_dist = Coord::manhatten_dist(self, candidate);
            if next_dist < closest_distance {
-               closest_point = Some(candidate.clone());
+               closest_point = Some(*candidate);
                closest_distance = next_dist;
            }
         }
@@ -65,6 +66,7 @@ impl Coord {
         }
     }
 
+    #[allow(dead_code)]
     fn mult(&self, other : &Coord) -> Self{
         Self{
             x : self.x * other.x,
@@ -91,7 +93,7 @@ struct Segment{
 }
 
 impl Segment{
-    fn new<'a>(a : Coord, b : Coord) -> Self {
+    fn new(a : Coord, b : Coord) -> Self {
        Self {a, b}
     }
 
@@ -130,12 +132,10 @@ impl Segment{
     }
 
     fn on_segment(&self, coord: &Coord) -> bool {
-        (
-                coord.x <= f32::max(self.a.x, self.b.x) &&
+        coord.x <= f32::max(self.a.x, self.b.x) &&
                 coord.x >= f32::min(self.a.x, self.b.x) &&
                 coord.y <= f32::max(self.a.y, self.b.y) &&
                 coord.y >= f32::min(self.a.y, self.b.y)
-        )
     }
 }
 
@@ -144,7 +144,7 @@ fn is_zero(n :f32) -> bool {
 }
 
 fn between_one_and_zero(n : f32) -> bool {
-    0.0 <= n && n <= 1.0
+    (0.0..=1.0).contains(&n)
 }
 
 fn parse_direction_coord(input : &str) -> DirectionCoord {
@@ -158,15 +158,15 @@ fn extract_direction_coords_from_line(line : &str) -> Vec<DirectionCoord> {
     line.split(",").map(parse_direction_coord).collect()
 }
 
-fn direction_coords_to_coord_list(dir_coords : &Vec<DirectionCoord>) -> Vec<Coord> {
-    dir_coords.into_iter().fold(vec![], |mut coord_list, dir_coord| {
-        let last_coord :Coord  = if coord_list.len() == 0 {
+fn direction_coords_to_coord_list(dir_coords : &[DirectionCoord]) -> Vec<Coord> {
+    dir_coords.iter().fold(vec![], |mut coord_list, dir_coord| {
+        let last_coord :Coord  = if coord_list.is_empty() {
             Coord::zero()
         } else {
             coord_list[coord_list.len()-1]
         };
 
-        let next_coord = Coord::next(&last_coord, &dir_coord).unwrap();;
+        let next_coord = Coord::next(&last_coord, dir_coord).unwrap();
 
         coord_list.push(next_coord);
 
@@ -180,37 +180,37 @@ fn create_wire(input : &str) -> Vec<Coord> {
     direction_coords_to_coord_list(&dir_coords)
 }
 
-fn get_wires_from_input() -> (Vec<Coord>, Vec<Coord>){
-    let input = read_input_file("day-3-part-1-input");
+fn get_wires_from_input() -> io::Result<(Vec<Coord>, Vec<Coord>)>{
+    let input = read_input_file("day-3-part-1-input")?;
     let raw_wires : Vec<&str> = input.split("\n").collect();
 
-    (
+    Ok((
         create_wire(raw_wires[0]),
         create_wire(raw_wires[1])
-    )
+    ))
 }
 
-fn collect_segments(coord_list : &Vec<Coord>) -> Vec<Segment> {
+fn collect_segments(coord_list : &[Coord]) -> Vec<Segment> {
     let mut i = 0;
 
-    coord_list.into_iter().fold(vec![], |mut segments, coord|{
+    coord_list.iter().fold(vec![], |mut segments, coord|{
         let next_idx = (i + 1) % coord_list.len();
         let next_coord = coord_list[next_idx];
 
-        segments.push( Segment::new(coord.clone(), next_coord.clone()));
+        segments.push( Segment::new(*coord, next_coord));
         i += 1;
 
         segments
     })
 }
 
-fn count_steps_to_point(segment_list : &Vec<Segment>, point : &Coord) -> f32 {
+fn count_steps_to_point(segment_list : &[Segment], point : &Coord) -> f32 {
     let mut steps = segment_list[0].a.max();
     let mut step_list = vec![steps];
 
     for segment in segment_list{
         if segment.on_segment(point){
-            let sub_seg = Segment::new(segment.a, point.clone());
+            let sub_seg = Segment::new(segment.a, *point);
             step_list.push(sub_seg.get_steps());
             steps += sub_seg.get_steps();
             break;
@@ -225,9 +225,9 @@ fn count_steps_to_point(segment_list : &Vec<Segment>, point : &Coord) -> f32 {
 }
 
 
-fn find_intersections(coord_list_a : &Vec<Coord>, coord_list_b : &Vec<Coord>) -> Vec<(Coord, f32)> {
-    let segment_list_a = collect_segments(&coord_list_a);
-    let segment_list_b = collect_segments(&coord_list_b);
+fn find_intersections(coord_list_a : &[Coord], coord_list_b : &[Coord]) -> Vec<(Coord, f32)> {
+    let segment_list_a = collect_segments(coord_list_a);
+    let segment_list_b = collect_segments(coord_list_b);
 
     let mut intersections : Vec<(Coord, f32)> = vec![];
 
@@ -247,7 +247,7 @@ fn find_intersections(coord_list_a : &Vec<Coord>, coord_list_b : &Vec<Coord>) ->
 }
 
 fn find_fewest_steps_intersection(intersections : Vec<(Coord, f32)>) -> (Option<Coord>, f32){
-    let mut fewest_steps = std::f32::INFINITY;
+    let mut fewest_steps = f32::INFINITY;
     let mut closest_intersection = None;
 
     for (coord, steps) in intersections {
@@ -260,26 +260,30 @@ fn find_fewest_steps_intersection(intersections : Vec<(Coord, f32)>) -> (Option<
     (closest_intersection, fewest_steps)
 }
 
-fn run_part_1(){
-    let (wire_a, wire_b) = get_wires_from_input();
+fn run_part_1() -> io::Result<()>{
+    let (wire_a, wire_b) = get_wires_from_input()?;
     let intersections = find_intersections(&wire_a, &wire_b).into_iter().map(|(intersection,_)|intersection).collect();
 
     println!("Answer part 1: {:?}", Coord::zero().closest_to(&intersections));
+
+    Ok(())
 }
 
-fn run_part_2(){
-    let (wire_a, wire_b) = get_wires_from_input();
+fn run_part_2() -> io::Result<()>{
+    let (wire_a, wire_b) = get_wires_from_input()?;
     let intersections = find_intersections(&wire_a, &wire_b);
     let (_, steps) = find_fewest_steps_intersection(intersections);
 
 
     println!("Answer part 2: {}", steps);
+
+    Ok(())
 }
 
 
-fn main(){
-    run_part_1();
-    run_part_2();
+fn main() -> io::Result<()>{
+    run_part_1()?;
+    run_part_2()
 }
 
 #[cfg(test)]
@@ -369,7 +373,7 @@ mod day_3_tests{
         );
 
         match seg1.get_intersection_point(&seg2) {
-            None => assert!(true),
+            None => {},
             _ => panic!("Received invalid result")
         }
     }
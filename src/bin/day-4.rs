@@ -1,5 +1,3 @@
-use std::borrow::Borrow;
-use std::collections::HashMap;
 
 struct PassCoderator{
     digits : Vec<i32>
@@ -1,8 +1,12 @@
 use adventofcode::int_code_computer::IntCodeMachine;
+use adventofcode::int_code_computer::io::{StdinSource, StdoutSink};
 
 
 fn main(){
-    let mut program = IntCodeMachine::read_file_into_program("day-5-part-1-input");
+    let program : Vec<i64> = IntCodeMachine::read_file_into_program("day-5-part-1-input").unwrap();
+    let mut machine = IntCodeMachine::new(&program, None);
 
-    IntCodeMachine::run_program(&mut program, None);
+    machine.set_input_source(StdinSource);
+    machine.set_output_sink(StdoutSink);
+    machine.run().unwrap();
 }
\ No newline at end of file
@@ -1,11 +1,8 @@
 use petgraph::{Graph, graph::NodeIndex};
 use adventofcode::read_input_file;
-use std::collections::{HashMap, HashSet};
-use adventofcode::int_code_computer::Opcode::ProgramEnd;
+use std::collections::HashMap;
 use std::ops::Index;
-use petgraph::visit::NodeIndexable;
-use petgraph::graph::Node;
-use std::hash::Hash;
+use std::io;
 
 type OrbitData = (String, String);
 
@@ -19,7 +16,7 @@ fn count_orbits(graph : &Graph<String, String>, node_index : NodeIndex) -> i32{
         if neighbors.len() > 1{
             panic!("Something terrible happened");
         }
-        if neighbors.len() == 0{
+        if neighbors.is_empty(){
             break;
         }
 
@@ -34,7 +31,7 @@ fn sum_orbits(graph : &Graph<String, String>) -> i32{
     let mut orbits = 0;
 
     for node_index in graph.node_indices(){
-        let o = count_orbits(&graph, node_index);
+        let o = count_orbits(graph, node_index);
 
         orbits += o;
     }
@@ -46,7 +43,7 @@ fn orbits_between(graph : &Graph<String, String>, a : NodeIndex, b : NodeIndex)
     let mut a_idx =  graph.neighbors(a).next().unwrap();
     let mut b_idx = graph.neighbors(b).next().unwrap();
 
-    let mut transfers_between = 0;
+    let transfers_between;
 
     let mut a_steps = 0;
     let mut b_steps = 0;
@@ -159,11 +156,13 @@ fn part_2(input : String){
     println!("Distance between you and santa: {}", distance)
 }
 
-fn main(){
-    let input = read_input_file("day-6-part-1-input");
+fn main() -> io::Result<()>{
+    let input = read_input_file("day-6-part-1-input")?;
 
     part_1(input.clone());
     part_2(input.clone());
+
+    Ok(())
 }
 
 
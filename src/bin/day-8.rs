@@ -1,4 +1,5 @@
 use adventofcode::read_input_file;
+use std::io;
 
 type Layer = Vec<i32>;
 type Image = Vec<Layer>;
@@ -41,7 +42,7 @@ fn layer_with_fewest_digits(image : &Image, digit : i32) -> &Layer {
     for layer in image{
         let next = count_digits_in_layer(layer, digit);
 
-        if(next < fewest){
+        if next < fewest {
             final_layer = layer;
             fewest = next;
         }
@@ -89,8 +90,8 @@ fn display_layer(layer : &Layer, width : i32){
     println!("{}", line.join(""));
 }
 
-fn part_one(){
-    let file = read_input_file("day-8-part-1-input");
+fn part_one() -> io::Result<()>{
+    let file = read_input_file("day-8-part-1-input")?;
     let image_stream: Vec<i32> = file.chars().map(|x| x.to_digit(10).unwrap() as i32).collect();
     let image = read_image_stream(6, 25, &image_stream);
 
@@ -99,20 +100,23 @@ fn part_one(){
 
     println!("Part 1: {}", ones_times_twos);
 
+    Ok(())
 }
 
-fn part_two(){
-    let file = read_input_file("day-8-part-1-input");
+fn part_two() -> io::Result<()>{
+    let file = read_input_file("day-8-part-1-input")?;
     let image_stream: Vec<i32> = file.chars().map(|x| x.to_digit(10).unwrap() as i32).collect();
     let image = read_image_stream(6, 25, &image_stream);
 
     let collapsed = collapse_layers(&image);
     display_layer(&collapsed, 25);
+
+    Ok(())
 }
 
-fn main(){
-    part_one();
-    part_two();
+fn main() -> io::Result<()>{
+    part_one()?;
+    part_two()
 }
 
 
@@ -1,21 +1,21 @@
 use adventofcode::int_code_computer::IntCodeMachine;
 
 fn part_one() -> Vec<i64>{
-    let program = IntCodeMachine::read_file_into_program("day-9-part-1-input");
+    let program : Vec<i64> = IntCodeMachine::read_file_into_program("day-9-part-1-input").unwrap();
     let input = vec![1];
     let mut machine = IntCodeMachine::new(&program, Some(&input));
 
-    machine.run();
+    machine.run().unwrap();
 
     machine.output.clone()
 }
 
 fn part_two() -> Vec<i64>{
-    let program = IntCodeMachine::read_file_into_program("day-9-part-1-input");
+    let program : Vec<i64> = IntCodeMachine::read_file_into_program("day-9-part-1-input").unwrap();
     let input = vec![2];
     let mut machine = IntCodeMachine::new(&program, Some(&input));
 
-    machine.run();
+    machine.run().unwrap();
 
     machine.output.clone()
 }
@@ -0,0 +1,327 @@
+use adventofcode::int_code_computer::{IntCodeMachine, MachineState};
+use adventofcode::int_code_computer::debugger::Access;
+use adventofcode::int_code_computer::disassembler::{decode_with, ListingEntry};
+use adventofcode::int_code_computer::loader::{load_path, LoadError};
+use std::env;
+use std::io::{self, BufRead, Write};
+
+const HELP : &str = "\
+step [n]             execute n instructions (default 1, at most 100000), ignoring breakpoints
+continue             run until a break, input is needed, or the program halts
+break [addr]         add a breakpoint, or list breakpoints and watches
+delete <addr>        remove a breakpoint
+watch <addr> [r|w]   break after a read and/or write of a cell (default both)
+unwatch <addr>       stop watching a cell
+breakop <opcode>     break before every instruction with this opcode
+breakio on|off       break after every INPUT and OUTPUT
+x <addr> [n]         show n memory cells (default 1, at most 1024)
+set <addr> <value>   write a memory cell
+regs                 show pc, relative base, state and cycle count
+input <v> [v...]     queue input values
+output               show everything the program has output
+list [n]             disassemble n entries either side of pc (default 5)
+quit";
+
+// Most cells `x` will show at once.
+const MAX_CELLS : usize = 1024;
+// Most instructions a single `step` will execute.
+const MAX_STEPS : usize = 100_000;
+
+struct Session {
+    machine : IntCodeMachine,
+    shown_output : usize
+}
+
+fn parse<T : std::str::FromStr>(token : Option<&&str>, what : &str) -> Result<T, String> {
+    let token = token.ok_or_else(|| format!("missing {}", what))?;
+
+    token.parse::<T>().map_err(|_| format!("bad {}: {:?}", what, token))
+}
+
+impl Session {
+    fn new(program : &[i64]) -> Self {
+        Self { machine : IntCodeMachine::new(program, None), shown_output : 0 }
+    }
+
+    // Output produced since the last time we showed any.
+    fn new_output(&mut self) -> String {
+        let fresh = &self.machine.output[self.shown_output..];
+        let text = fresh.iter().map(|value| format!("output: {}\n", value)).collect();
+
+        self.shown_output = self.machine.output.len();
+        text
+    }
+
+    fn stopped(&mut self) -> String {
+        let state = match self.machine.last_break() {
+            Some(reason) => format!("break: {:?}", reason),
+            None => format!("{:?}", self.machine.state())
+        };
+
+        format!("{}{} at pc {}", self.new_output(), state, self.machine.program_counter())
+    }
+
+    fn list(&self, context : usize) -> String {
+        let pc = self.machine.program_counter();
+        let listing = self.machine.disassemble();
+        let mut text = String::new();
+
+        if let Some((opcode, operands)) = decode_with(self.machine.memory.image(), pc, self.machine.registry()) {
+            let entry = ListingEntry::Instruction { address : pc, opcode, operands };
+
+            if listing.entry_at(pc) != Some(&entry) {
+                text.push_str(&format!("=> {:>5}: {}  (not in the static listing)\n", pc, entry));
+            }
+        }
+
+        let current = listing.entries.iter().position(|entry| entry.address() <= pc && pc < entry.address() + entry.len())
+            .unwrap_or(listing.entries.len());
+        let start = current.saturating_sub(context);
+        let end = listing.entries.len().min(current.saturating_add(context).saturating_add(1));
+
+        for entry in listing.entries[start..end].iter() {
+            let marker = match entry.address() == pc {
+                true => "=>",
+                false => "  "
+            };
+
+            text.push_str(&format!("{} {:>5}: {}\n", marker, entry.address(), entry));
+        }
+
+        text
+    }
+
+    fn command(&mut self, line : &str) -> Result<String, String> {
+        let words : Vec<&str> = line.split_whitespace().collect();
+        let args = &words[1.min(words.len())..];
+
+        match words.first().copied().unwrap_or("") {
+            "" => Ok(String::new()),
+            "help" | "h" => Ok(HELP.to_string()),
+            "step" | "s" => {
+                let count = match args.first() {
+                    Some(_) => parse::<usize>(args.first(), "count")?,
+                    None => 1
+                };
+                let mut text = String::new();
+
+                if count > MAX_STEPS {
+                    return Err(format!("count {} is more than {}", count, MAX_STEPS));
+                }
+
+                for _ in 0..count {
+                    let step = self.machine.step().map_err(|err| err.to_string())?;
+
+                    text.push_str(&format!("{}\n{}", step, self.new_output()));
+
+                    if step.state != MachineState::Running {
+                        text.push_str(&format!("{:?}\n", step.state));
+                        break;
+                    }
+                }
+
+                Ok(text)
+            },
+            "continue" | "c" => {
+                self.machine.run().map_err(|err| format!("{}{}", self.new_output(), err))?;
+
+                Ok(self.stopped())
+            },
+            "break" | "b" => match args.first() {
+                Some(_) => {
+                    let address = parse::<usize>(args.first(), "address")?;
+
+                    self.machine.breakpoints_mut().add(address);
+                    Ok(format!("breakpoint at {}", address))
+                },
+                None => {
+                    let breakpoints = self.machine.breakpoints();
+                    let mut text : String = breakpoints.addresses().map(|address| format!("break {}\n", address)).collect();
+
+                    text.extend(breakpoints.watches().map(|(address, access)| format!("watch {} {:?}\n", address, access)));
+                    Ok(text)
+                }
+            },
+            "delete" | "d" => {
+                let address = parse::<usize>(args.first(), "address")?;
+
+                match self.machine.breakpoints_mut().remove(address) {
+                    true => Ok(format!("removed breakpoint at {}", address)),
+                    false => Err(format!("no breakpoint at {}", address))
+                }
+            },
+            "watch" | "w" => {
+                let address = parse::<usize>(args.first(), "address")?;
+                let accesses = match args.get(1).copied() {
+                    None | Some("rw") => vec![Access::Read, Access::Write],
+                    Some("r") => vec![Access::Read],
+                    Some("w") => vec![Access::Write],
+                    Some(other) => return Err(format!("bad access {:?}, expected r, w or rw", other))
+                };
+
+                for access in accesses {
+                    self.machine.breakpoints_mut().watch(address, access);
+                }
+
+                Ok(format!("watching {}", address))
+            },
+            "unwatch" => {
+                let address = parse::<usize>(args.first(), "address")?;
+
+                self.machine.breakpoints_mut().unwatch(address);
+                Ok(format!("stopped watching {}", address))
+            },
+            "breakop" => {
+                let opcode = parse(args.first(), "opcode")?;
+
+                self.machine.breakpoints_mut().break_on(opcode);
+                Ok(format!("breaking on {}", opcode))
+            },
+            "breakio" => {
+                let enabled = match args.first().copied() {
+                    Some("on") => true,
+                    Some("off") => false,
+                    _ => return Err(String::from("expected on or off"))
+                };
+
+                self.machine.breakpoints_mut().set_break_on_io(enabled);
+                Ok(format!("break on I/O {}", args[0]))
+            },
+            "x" => {
+                let address = parse::<usize>(args.first(), "address")?;
+                let count = match args.get(1) {
+                    Some(_) => parse::<usize>(args.get(1), "count")?,
+                    None => 1
+                };
+
+                if count > MAX_CELLS {
+                    return Err(format!("count {} is more than {}", count, MAX_CELLS));
+                }
+
+                let end = address.checked_add(count).ok_or_else(|| format!("address {} + {} is out of range", address, count))?;
+
+                Ok((address..end).map(|cell| format!("{:>5}: {}\n", cell, self.machine.memory.read(cell))).collect())
+            },
+            "set" => {
+                let address = parse::<usize>(args.first(), "address")?;
+                let value = parse::<i64>(args.get(1), "value")?;
+
+                self.machine.memory.write(address, value);
+                Ok(format!("{:>5}: {}", address, value))
+            },
+            "regs" | "r" => Ok(format!(
+                "pc {}\nrb {}\nstate {:?}\ncycles {}",
+                self.machine.program_counter(), self.machine.relative_base(), self.machine.state(), self.machine.cycles()
+            )),
+            "input" | "i" => {
+                let values = args.iter()
+                    .map(|token| token.parse::<i64>().map_err(|_| format!("bad value: {:?}", token)))
+                    .collect::<Result<Vec<i64>, String>>()?;
+
+                self.machine.push_inputs(&values).map_err(|err| err.to_string())?;
+                Ok(format!("queued {} value(s)", values.len()))
+            },
+            "output" | "o" => {
+                self.shown_output = self.machine.output.len();
+
+                Ok(self.machine.output.iter().map(|value| value.to_string()).collect::<Vec<String>>().join(","))
+            },
+            "list" | "l" => {
+                let context = match args.first() {
+                    Some(_) => parse::<usize>(args.first(), "count")?,
+                    None => 5
+                };
+
+                Ok(self.list(context))
+            },
+            other => Err(format!("unknown command {:?}, try help", other))
+        }
+    }
+}
+
+fn main(){
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: intcode-debugger <program file>");
+            std::process::exit(2);
+        }
+    };
+    let program : Vec<i64> = load_path(&path).unwrap_or_else(|err| {
+        match err {
+            LoadError::Io { .. } => eprintln!("{}", err),
+            LoadError::Syntax { .. } => eprintln!("{}:{}", path, err)
+        }
+
+        std::process::exit(1);
+    });
+    let mut session = Session::new(&program);
+    let stdin = io::stdin();
+
+    println!("loaded {} words from {}, type help for commands", program.len(), path);
+
+    loop {
+        print!("(intcode) ");
+        io::stdout().flush().unwrap();
+
+        let mut line = String::new();
+
+        if stdin.lock().read_line(&mut line).unwrap() == 0 || matches!(line.trim(), "quit" | "q") {
+            break;
+        }
+
+        match session.command(&line) {
+            Ok(text) => println!("{}", text.trim_end()),
+            Err(message) => println!("error: {}", message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod intcode_debugger_tests{
+    use crate::Session;
+
+    // Reads a value, doubles it and outputs it.
+    const DOUBLE : [i64; 10] = [3,9,1002,9,2,9,4,9,99,0];
+
+    #[test]
+    fn break_and_continue(){
+        let mut session = Session::new(&DOUBLE);
+
+        session.command("break 6").unwrap();
+        session.command("input 21").unwrap();
+
+        assert_eq!(session.command("continue").unwrap(), "break: Breakpoint { pc: 6 } at pc 6");
+        assert_eq!(session.command("x 9").unwrap(), "    9: 42\n");
+        assert_eq!(session.command("set 9 5").unwrap(), "    9: 5");
+        assert_eq!(session.command("c").unwrap(), "output: 5\nHalted at pc 8");
+        assert_eq!(session.command("output").unwrap(), "5");
+    }
+
+    #[test]
+    fn step_regs_and_list(){
+        let mut session = Session::new(&DOUBLE);
+
+        assert_eq!(session.command("step").unwrap(), "0: INPUT, (0, (&9, MODE:0))\nAwaitingInput\n");
+
+        session.command("i 4").unwrap();
+
+        assert_eq!(session.command("s 2").unwrap().lines().count(), 2);
+        assert!(session.command("regs").unwrap().starts_with("pc 6\nrb 0\nstate Running\ncycles 2"));
+        assert_eq!(session.command("list 1").unwrap(), "       2: MULT [9], #2, [9]\n=>     6: OUTPUT [9]\n       8: HALT\n");
+    }
+
+    #[test]
+    fn rejects_bad_commands(){
+        let mut session = Session::new(&DOUBLE);
+
+        assert!(session.command("frobnicate").is_err());
+        assert!(session.command("x nine").is_err());
+        assert!(session.command("x 18446744073709551615 2").is_err());
+        assert!(session.command("x 0 1000000000").is_err());
+        assert!(session.command("step 18446744073709551615").is_err());
+        assert!(session.command("list 18446744073709551615").is_ok());
+        assert!(session.command("watch 9 x").is_err());
+        assert!(session.command("breakop NOPE").is_err());
+    }
+}
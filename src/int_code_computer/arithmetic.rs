@@ -0,0 +1,62 @@
+/// How `ADD`, `MULT`, `RBO` and relative addressing treat results that don't fit in the
+/// machine's word. Whichever is picked, the behaviour is the same in debug and release builds.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub enum Arithmetic {
+    /// Two's complement wrap-around, what a release build did before.
+    #[default]
+    Wrapping,
+    /// Fault with `IntcodeError::ArithmeticOverflow`.
+    Checked,
+    /// Clamp to the smallest or largest word.
+    Saturating
+}
+
+#[cfg(test)]
+mod arithmetic_tests {
+    use crate::int_code_computer::arithmetic::*;
+    use crate::int_code_computer::engine::Engine;
+    use crate::int_code_computer::word::Word;
+    use crate::int_code_computer::{IntCodeMachine, IntcodeError, MachineState};
+    use crate::int_code_computer::fixtures::SQUARE;
+
+    fn square(arithmetic: Arithmetic, engine: Engine, value: i64) -> (Result<MachineState, IntcodeError>, Vec<i64>) {
+        let mut machine = IntCodeMachine::new(&SQUARE, Some(&vec![value]));
+
+        machine.set_arithmetic(arithmetic);
+        machine.set_engine(engine);
+
+        (machine.run(), machine.output)
+    }
+
+    #[test]
+    fn policies(){
+        assert_eq!(i64::MAX.add(&1, Arithmetic::Wrapping), Some(i64::MIN));
+        assert_eq!(i64::MAX.add(&1, Arithmetic::Checked), None);
+        assert_eq!((1i64 << 31).mul(&(1 << 31), Arithmetic::Checked), Some(1 << 62));
+        assert_eq!(i64::MIN.mul(&2, Arithmetic::Saturating), Some(i64::MIN));
+    }
+
+    #[test]
+    fn machine_applies_policy_in_both_engines(){
+        for engine in [Engine::Interpreter, Engine::Predecoded].iter() {
+            let big = 1 << 40;
+
+            assert_eq!(square(Arithmetic::Wrapping, *engine, big), (Ok(MachineState::Halted), vec![0]));
+            assert_eq!(square(Arithmetic::Saturating, *engine, -big), (Ok(MachineState::Halted), vec![i64::MAX]));
+            assert_eq!(
+                square(Arithmetic::Checked, *engine, big),
+                (Err(IntcodeError::ArithmeticOverflow { pc : 2, instruction : 2 }), vec![])
+            );
+            assert_eq!(square(Arithmetic::Checked, *engine, 1 << 20), (Ok(MachineState::Halted), vec![1 << 40]));
+        }
+    }
+
+    #[test]
+    fn relative_base_overflow(){
+        let mut machine = IntCodeMachine::new(&[109,i64::MAX,109,1,99], None);
+
+        machine.set_arithmetic(Arithmetic::Checked);
+
+        assert_eq!(machine.run(), Err(IntcodeError::ArithmeticOverflow { pc : 2, instruction : 109 }));
+    }
+}
@@ -0,0 +1,388 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::int_code_computer::Opcode;
+
+/// Assembler source syntax, one statement per line:
+///
+/// ```text
+/// start:  in [x]              ; comments run to the end of the line
+/// loop:   add [x], #-1, [x]
+///         out [rb+2]
+///         jt [x], @loop       ; @label is the label's address in immediate mode
+///         jt @start           ; one-operand jumps are unconditional
+///         halt
+/// x:      data 3, start, loop+1
+/// ```
+///
+/// Operands are `#value` (immediate), `@label` (immediate), `[value]` (position) and
+/// `[rb+n]` (relative). A value is a number, a label, or a label plus/minus a number.
+/// Mnemonics are the `Opcode` display names (ADD, MULT, JUMP_TRUE, ...) in any case,
+/// plus the short forms in, out, jt, jf, lt, eq, mul and hlt.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AssembleError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String
+}
+
+impl AssembleError {
+    fn new(line: usize, column: usize, message: String) -> Self {
+        Self { line, column, message }
+    }
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl std::error::Error for AssembleError {}
+
+#[derive(Clone, Debug)]
+enum Value {
+    Literal(i64),
+    Label { name: String, offset: i64 }
+}
+
+#[derive(Clone, Debug)]
+struct Operand {
+    mode: i64,
+    value: Value,
+    column: usize
+}
+
+#[derive(Clone, Debug)]
+enum Statement {
+    Instruction { opcode: Opcode, operands: Vec<Operand> },
+    Data(Vec<Operand>)
+}
+
+pub type SymbolTable = BTreeMap<String, usize>;
+
+pub fn assemble(source: &str) -> Result<Vec<i64>, AssembleError> {
+    assemble_with_symbols(source).map(|(program, _)| program)
+}
+
+/// Assemble `source`, also returning the address of every label.
+pub fn assemble_with_symbols(source: &str) -> Result<(Vec<i64>, SymbolTable), AssembleError> {
+    let mut symbols = SymbolTable::new();
+    let mut statements = vec![];
+    let mut address = 0;
+
+    // First pass: parse and lay out, so labels can be used before they're defined.
+    for (index, text) in source.lines().enumerate() {
+        let line = index + 1;
+        let text = match text.find(';') {
+            Some(comment) => &text[..comment],
+            None => text
+        };
+        let mut position = skip_whitespace(text, 0);
+
+        loop {
+            let word_end = identifier_end(text, position);
+            let after_word = skip_whitespace(text, word_end);
+
+            if word_end == position || !text[after_word..].starts_with(':') {
+                break;
+            }
+
+            let name = &text[position..word_end];
+
+            if symbols.insert(name.to_string(), address).is_some() {
+                return Err(AssembleError::new(line, position + 1, format!("label {:?} is already defined", name)));
+            }
+
+            position = skip_whitespace(text, after_word + 1);
+        }
+
+        if position == text.len() {
+            continue;
+        }
+
+        let mnemonic_end = identifier_end(text, position);
+        let mnemonic = &text[position..mnemonic_end];
+        let is_data = mnemonic.eq_ignore_ascii_case("data");
+        let opcode = match is_data {
+            true => None,
+            false => Some(parse_mnemonic(mnemonic)
+                .ok_or_else(|| AssembleError::new(line, position + 1, format!("unknown mnemonic {:?}", mnemonic)))?)
+        };
+        let operands = parse_operands(text, mnemonic_end, line, is_data)?;
+
+        let statement = match opcode {
+            Some(opcode) => Statement::Instruction { opcode, operands: check_operands(opcode, operands, line, position + 1)? },
+            None => Statement::Data(operands)
+        };
+
+        address += match statement {
+            Statement::Instruction { opcode, .. } => opcode.get_size(),
+            Statement::Data(ref values) => values.len()
+        };
+
+        statements.push((line, statement));
+    }
+
+    // Second pass: encode with every label known.
+    let mut program = vec![];
+
+    for (line, statement) in statements {
+        match statement {
+            Statement::Instruction { opcode, operands } => {
+                let modes = operands.iter().rev().fold(0, |modes, operand| modes * 10 + operand.mode);
+
+                program.push(opcode.code() + modes * 100);
+
+                for operand in operands {
+                    program.push(resolve(&operand, &symbols, line)?);
+                }
+            },
+            Statement::Data(values) => {
+                for value in values {
+                    program.push(resolve(&value, &symbols, line)?);
+                }
+            }
+        }
+    }
+
+    Ok((program, symbols))
+}
+
+fn parse_mnemonic(mnemonic: &str) -> Option<Opcode> {
+    let alias = match mnemonic.to_ascii_lowercase().as_str() {
+        "in" => Some(Opcode::Input),
+        "out" => Some(Opcode::Output),
+        "jt" => Some(Opcode::JumpIfTrue),
+        "jf" => Some(Opcode::JumpIfFalse),
+        "lt" => Some(Opcode::LessThan),
+        "eq" => Some(Opcode::Equals),
+        "mul" => Some(Opcode::Mult),
+        "hlt" => Some(Opcode::ProgramEnd),
+        _ => None
+    };
+
+    alias.or_else(|| mnemonic.parse::<Opcode>().ok())
+}
+
+fn check_operands(opcode: Opcode, mut operands: Vec<Operand>, line: usize, column: usize) -> Result<Vec<Operand>, AssembleError> {
+    let expected = opcode.get_size() - 1;
+
+    // `jt @target` / `jf @target` are unconditional jumps.
+    if operands.len() == 1 && (opcode == Opcode::JumpIfTrue || opcode == Opcode::JumpIfFalse) {
+        let condition = if opcode == Opcode::JumpIfTrue { 1 } else { 0 };
+
+        operands.insert(0, Operand { mode: 1, value: Value::Literal(condition), column });
+    }
+
+    if operands.len() != expected {
+        return Err(AssembleError::new(line, column, format!("{} expects {} operands, found {}", opcode, expected, operands.len())));
+    }
+
+    for index in opcode.write_operands() {
+        if operands[index].mode == 1 {
+            return Err(AssembleError::new(line, operands[index].column, format!("{} cannot write to an immediate operand", opcode)));
+        }
+    }
+
+    Ok(operands)
+}
+
+fn parse_operands(text: &str, start: usize, line: usize, is_data: bool) -> Result<Vec<Operand>, AssembleError> {
+    let mut operands = vec![];
+    let mut position = start;
+
+    if skip_whitespace(text, start) == text.len() {
+        return Ok(operands);
+    }
+
+    for piece in text[start..].split(',') {
+        let column = skip_whitespace(text, position) + 1;
+
+        let operand = match is_data {
+            true => Operand { mode: 0, value: parse_value(piece, line, column)?, column },
+            false => parse_operand(piece.trim(), line, column)?
+        };
+
+        operands.push(operand);
+        position += piece.len() + 1;
+    }
+
+    Ok(operands)
+}
+
+fn parse_operand(text: &str, line: usize, column: usize) -> Result<Operand, AssembleError> {
+    if let Some(value) = text.strip_prefix('#').or_else(|| text.strip_prefix('@')) {
+        return Ok(Operand { mode: 1, value: parse_value(value, line, column + 1)?, column });
+    }
+
+    if let Some(inner) = text.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        let inner = inner.trim();
+
+        if inner.get(..2).is_some_and(|head| head.eq_ignore_ascii_case("rb")) && identifier_end(inner, 0) == 2 {
+            let offset = inner[2..].replace(' ', "");
+            let offset = match offset.strip_prefix('+') {
+                _ if offset.is_empty() => Ok(0),
+                Some(positive) => positive.parse::<i64>(),
+                None => offset.parse::<i64>()
+            };
+
+            return offset
+                .map(|offset| Operand { mode: 2, value: Value::Literal(offset), column })
+                .map_err(|_| AssembleError::new(line, column, format!("invalid relative offset in {:?}", text)));
+        }
+
+        return Ok(Operand { mode: 0, value: parse_value(inner, line, column + 1)?, column });
+    }
+
+    Err(AssembleError::new(line, column, format!("expected #value, @label, [address] or [rb+n], found {:?}", text)))
+}
+
+fn parse_value(text: &str, line: usize, column: usize) -> Result<Value, AssembleError> {
+    let text = text.trim();
+
+    if let Ok(literal) = text.parse::<i64>() {
+        return Ok(Value::Literal(literal));
+    }
+
+    let name_end = identifier_end(text, 0);
+    let invalid = || AssembleError::new(line, column, format!("invalid value {:?}", text));
+
+    if name_end == 0 || text.as_bytes()[0].is_ascii_digit() {
+        return Err(invalid());
+    }
+
+    let offset = text[name_end..].replace(' ', "");
+    let offset = match offset.strip_prefix('+') {
+        _ if offset.is_empty() => 0,
+        Some(positive) => positive.parse::<i64>().map_err(|_| invalid())?,
+        None if offset.starts_with('-') => offset.parse::<i64>().map_err(|_| invalid())?,
+        None => return Err(invalid())
+    };
+
+    Ok(Value::Label { name: text[..name_end].to_string(), offset })
+}
+
+fn resolve(operand: &Operand, symbols: &SymbolTable, line: usize) -> Result<i64, AssembleError> {
+    match &operand.value {
+        Value::Literal(value) => Ok(*value),
+        Value::Label { name, offset } => symbols.get(name)
+            .map(|address| *address as i64 + offset)
+            .ok_or_else(|| AssembleError::new(line, operand.column, format!("undefined label {:?}", name)))
+    }
+}
+
+fn skip_whitespace(text: &str, from: usize) -> usize {
+    text[from..].find(|c: char| !c.is_whitespace()).map_or(text.len(), |offset| from + offset)
+}
+
+fn identifier_end(text: &str, from: usize) -> usize {
+    text[from..].find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).map_or(text.len(), |offset| from + offset)
+}
+
+#[cfg(test)]
+mod assembler_tests {
+    use crate::int_code_computer::assembler::*;
+    use crate::int_code_computer::disassembler::disassemble;
+    use crate::int_code_computer::IntCodeMachine;
+
+    #[test]
+    fn encodes_parameter_modes(){
+        assert_eq!(
+            assemble("add [9], #5, [10]\nmult [rb-2], #3, [rb+4]\nrbo #-2\nhalt").unwrap(),
+            vec![1001, 9, 5, 10, 21202, -2, 3, 4, 109, -2, 99]
+        );
+    }
+
+    #[test]
+    fn resolves_forward_labels(){
+        let source = "
+            in [x]              ; countdown from the input
+    loop:   out [x]
+            add [x], #-1, [x]
+            jt [x], @loop
+            jt @done
+            data 0,0,0
+    done:   hlt
+    x:      data 0
+        ";
+        let (program, symbols) = assemble_with_symbols(source).unwrap();
+
+        assert_eq!(symbols["loop"], 2);
+        assert_eq!(symbols["done"], 17);
+        assert_eq!(program, vec![3, 18, 4, 18, 1001, 18, -1, 18, 1005, 18, 2, 1105, 1, 17, 0, 0, 0, 99, 0]);
+
+        let mut machine = IntCodeMachine::new(&program, Some(&vec![3]));
+
+        machine.run().unwrap();
+        assert_eq!(machine.output, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn labels_in_data_with_offsets(){
+        assert_eq!(
+            assemble("a: b: data b+1, a-1, c\nc: data 7").unwrap(),
+            vec![1, -1, 3, 7]
+        );
+    }
+
+    #[test]
+    fn reports_line_and_column(){
+        assert_eq!(
+            assemble("add [1], #2, [3]\n  frob [1]"),
+            Err(AssembleError { line: 2, column: 3, message: String::from("unknown mnemonic \"frob\"") })
+        );
+        assert_eq!(
+            assemble("halt\nin [nowhere]").unwrap_err(),
+            AssembleError { line: 2, column: 4, message: String::from("undefined label \"nowhere\"") }
+        );
+        assert_eq!(
+            assemble("add [1], #2, #3").unwrap_err(),
+            AssembleError { line: 1, column: 14, message: String::from("ADD cannot write to an immediate operand") }
+        );
+        assert_eq!(
+            assemble("out [1], [2]").unwrap_err(),
+            AssembleError { line: 1, column: 1, message: String::from("OUTPUT expects 1 operands, found 2") }
+        );
+        assert_eq!(
+            assemble("x: halt\nx: halt").unwrap_err(),
+            AssembleError { line: 2, column: 1, message: String::from("label \"x\" is already defined") }
+        );
+        assert_eq!(
+            assemble("out 5").unwrap_err(),
+            AssembleError { line: 1, column: 5, message: String::from("expected #value, @label, [address] or [rb+n], found \"5\"") }
+        );
+        assert_eq!(
+            assemble("out #1O1").unwrap_err(),
+            AssembleError { line: 1, column: 6, message: String::from("invalid value \"1O1\"") }
+        );
+    }
+
+    #[test]
+    fn rejects_non_ascii_operands(){
+        assert_eq!(
+            assemble("out [aé]").unwrap_err(),
+            AssembleError { line: 1, column: 6, message: String::from("invalid value \"aé\"") }
+        );
+        assert!(assemble("out [é]").is_err());
+        assert!(assemble("out [rbé]").is_err());
+        assert!(assemble("out #é").is_err());
+    }
+
+    #[test]
+    fn round_trips_through_the_disassembler(){
+        let source = "\
+JUMP_FALSE [20], #6
+OUTPUT [rb-3]
+HALT
+LESS_THAN [rb+0], #4, [21]
+EQUALS #1, [rb+2], [rb-1]
+RBO [5]
+INPUT [rb+7]
+JUMP_TRUE #1, #3
+DATA 42, 0
+";
+        let program = assemble(source).unwrap();
+
+        assert_eq!(disassemble(&program).to_source(), source);
+    }
+}
@@ -0,0 +1,343 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+use futures_core::Stream;
+use crate::int_code_computer::{IntCodeMachine, IntcodeError, StopReason};
+use crate::int_code_computer::word::Word;
+
+struct Shared<T> {
+    queue: VecDeque<T>,
+    waker: Option<Waker>,
+    senders: usize,
+    receiver: bool
+}
+
+/// The sending half of an unbounded single-threaded channel. Cloning adds a sender;
+/// the receiver sees the end of the channel once every sender is gone.
+pub struct Sender<T> {
+    shared: Rc<RefCell<Shared<T>>>
+}
+
+/// The receiving half, a `Stream` of everything sent.
+pub struct Receiver<T> {
+    shared: Rc<RefCell<Shared<T>>>
+}
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let shared = Rc::new(RefCell::new(Shared { queue: VecDeque::new(), waker: None, senders: 1, receiver: true }));
+
+    (Sender { shared: shared.clone() }, Receiver { shared })
+}
+
+impl<T> Sender<T> {
+    /// Hands the value back if the receiver has been dropped.
+    pub fn send(&self, value: T) -> Result<(), T> {
+        let mut shared = self.shared.borrow_mut();
+
+        if !shared.receiver {
+            return Err(value);
+        }
+
+        shared.queue.push_back(value);
+
+        if let Some(waker) = shared.waker.take() {
+            waker.wake();
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.borrow_mut().senders += 1;
+
+        Self { shared: self.shared.clone() }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        let mut shared = self.shared.borrow_mut();
+
+        shared.senders -= 1;
+
+        if shared.senders == 0 {
+            if let Some(waker) = shared.waker.take() {
+                waker.wake();
+            }
+        }
+    }
+}
+
+impl<T> Receiver<T> {
+    pub fn try_recv(&mut self) -> Option<T> {
+        self.shared.borrow_mut().queue.pop_front()
+    }
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut shared = self.shared.borrow_mut();
+
+        match shared.queue.pop_front() {
+            Some(value) => Poll::Ready(Some(value)),
+            None if shared.senders == 0 => Poll::Ready(None),
+            None => {
+                shared.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> Drop for Receiver<T> {
+    fn drop(&mut self) {
+        self.shared.borrow_mut().receiver = false;
+    }
+}
+
+/// A machine driven as a `Stream` of its outputs.
+///
+/// Whenever the program asks for input that isn't queued, the stream suspends until a
+/// value arrives on `inputs`. If every sender is gone by then, it yields
+/// `IntcodeError::InputExhausted` and ends; it also ends after any other error and
+/// once the program halts.
+pub struct AsyncMachine<W: Word = i64> {
+    machine: IntCodeMachine<W>,
+    inputs: Receiver<W>,
+    done: bool
+}
+
+impl<W: Word> AsyncMachine<W> {
+    pub fn new(machine: IntCodeMachine<W>, inputs: Receiver<W>) -> Self {
+        Self { machine, inputs, done: false }
+    }
+
+    pub fn machine(&self) -> &IntCodeMachine<W> {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut IntCodeMachine<W> {
+        &mut self.machine
+    }
+
+    pub fn into_inner(self) -> IntCodeMachine<W> {
+        self.machine
+    }
+
+    fn finish(&mut self, err: IntcodeError) -> Poll<Option<Result<W, IntcodeError>>> {
+        self.done = true;
+        Poll::Ready(Some(Err(err)))
+    }
+}
+
+impl<W: Word + Unpin> Stream for AsyncMachine<W> {
+    type Item = Result<W, IntcodeError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        while !self.done {
+            match self.machine.run_until_output() {
+                Ok(StopReason::Output(value)) => return Poll::Ready(Some(Ok(value))),
+                Ok(StopReason::Halted) => self.done = true,
+                Ok(StopReason::AwaitingInput) => match Pin::new(&mut self.inputs).poll_next(cx) {
+                    Poll::Ready(Some(value)) => {
+                        if let Err(err) = self.machine.push_input(value) {
+                            return self.finish(err);
+                        }
+                    },
+                    Poll::Ready(None) => {
+                        let err = IntcodeError::InputExhausted { pc: self.machine.program_counter() };
+
+                        return self.finish(err);
+                    },
+                    Poll::Pending => return Poll::Pending
+                },
+                // Breakpoints are for interactive use; a stream just carries on.
+                Ok(StopReason::Break(_)) => {},
+                Err(err) => return self.finish(err)
+            }
+        }
+
+        Poll::Ready(None)
+    }
+}
+
+/// Resolves to the next item of `stream`, or None once it has ended.
+pub struct Next<'a, S: Unpin> {
+    stream: &'a mut S
+}
+
+pub fn next<S: Stream + Unpin>(stream: &mut S) -> Next<'_, S> {
+    Next { stream }
+}
+
+impl<S: Stream + Unpin> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.stream).poll_next(cx)
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+/// Run `future` to completion on the current thread.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = Box::pin(future);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match future.as_mut().poll(&mut cx) {
+            Poll::Ready(output) => return output,
+            Poll::Pending => thread::park()
+        }
+    }
+}
+
+struct TaskWaker {
+    task: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.task);
+    }
+}
+
+type Task = Pin<Box<dyn Future<Output = ()>>>;
+
+/// A single-threaded executor for tasks that aren't `Send`, such as machines and the
+/// channels between them. Tasks are polled in spawn order, then whenever woken.
+#[derive(Default)]
+pub struct Executor {
+    tasks: Vec<Option<Task>>,
+    ready: Arc<Mutex<VecDeque<usize>>>
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn<F: Future<Output = ()> + 'static>(&mut self, task: F) {
+        self.ready.lock().unwrap().push_back(self.tasks.len());
+        self.tasks.push(Some(Box::pin(task)));
+    }
+
+    /// Poll tasks until all have finished or none can make progress. Returns how many
+    /// are still waiting, e.g. on input that will never come.
+    pub fn run(&mut self) -> usize {
+        loop {
+            let next = self.ready.lock().unwrap().pop_front();
+            let index = match next {
+                Some(index) => index,
+                None => return self.tasks.iter().filter(|task| task.is_some()).count()
+            };
+            let waker = Waker::from(Arc::new(TaskWaker { task: index, ready: self.ready.clone() }));
+            let mut cx = Context::from_waker(&waker);
+
+            if let Some(task) = self.tasks[index].as_mut() {
+                if task.as_mut().poll(&mut cx).is_ready() {
+                    self.tasks[index] = None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod async_machine_tests {
+    use crate::int_code_computer::async_machine::*;
+    use crate::int_code_computer::fixtures::FEEDBACK;
+
+    #[test]
+    fn outputs_as_stream(){
+        let (sender, receiver) = channel();
+        let mut machine = AsyncMachine::new(IntCodeMachine::new(&[3,9,1002,9,2,9,4,9,99,0], None), receiver);
+
+        sender.send(21).unwrap();
+        drop(sender);
+
+        assert_eq!(block_on(next(&mut machine)), Some(Ok(42)));
+        assert_eq!(block_on(next(&mut machine)), None);
+        assert_eq!(machine.machine().state(), &crate::int_code_computer::MachineState::Halted);
+    }
+
+    #[test]
+    fn closed_input_ends_stream(){
+        let (sender, receiver) = channel::<i64>();
+        let mut machine = AsyncMachine::new(IntCodeMachine::new(&[3,0,99], None), receiver);
+
+        drop(sender);
+
+        assert_eq!(block_on(next(&mut machine)), Some(Err(IntcodeError::InputExhausted { pc: 0 })));
+        assert_eq!(block_on(next(&mut machine)), None);
+    }
+
+    #[test]
+    fn feedback_loop_as_tasks(){
+        let mut executor = Executor::new();
+        let links : Vec<(Sender<i64>, Receiver<i64>)> = (0..5).map(|_| channel()).collect();
+        let (senders, receivers) : (Vec<_>, Vec<_>) = links.into_iter().unzip();
+        let result = Rc::new(RefCell::new(None));
+
+        for (index, receiver) in receivers.into_iter().enumerate() {
+            let mut amp = AsyncMachine::new(IntCodeMachine::new(&FEEDBACK, None), receiver);
+            let downstream = senders[(index + 1) % 5].clone();
+            let result = result.clone();
+
+            senders[index].send([9, 8, 7, 6, 5][index]).unwrap();
+
+            executor.spawn(async move {
+                while let Some(Ok(value)) = next(&mut amp).await {
+                    // The first amp has halted by the time the last one's final signal
+                    // comes round, so the send can fail.
+                    let _ = downstream.send(value);
+
+                    if index == 4 {
+                        *result.borrow_mut() = Some(value);
+                    }
+                }
+            });
+        }
+
+        senders[0].send(0).unwrap();
+        drop(senders);
+
+        assert_eq!(executor.run(), 0);
+        assert_eq!(*result.borrow(), Some(139629729));
+    }
+
+    #[test]
+    fn reports_stuck_tasks(){
+        let mut executor = Executor::new();
+        let (sender, receiver) = channel::<i64>();
+        let mut machine = AsyncMachine::new(IntCodeMachine::new(&[3,0,99], None), receiver);
+
+        executor.spawn(async move {
+            next(&mut machine).await;
+        });
+
+        assert_eq!(executor.run(), 1);
+
+        sender.send(1).unwrap();
+
+        assert_eq!(executor.run(), 0);
+    }
+}
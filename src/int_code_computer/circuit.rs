@@ -0,0 +1,301 @@
+use std::collections::VecDeque;
+use std::fmt;
+use crate::int_code_computer::{IntCodeMachine, IntcodeError, MachineState, StopReason};
+use crate::int_code_computer::word::Word;
+
+pub type NodeId = usize;
+pub type EdgeId = usize;
+
+/// How a node with several incoming edges picks its next input.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+pub enum Merge {
+    /// The oldest value on any incoming edge.
+    #[default]
+    Arrival,
+    /// One value from each incoming edge in the order they were connected, waiting for
+    /// the edge whose turn it is.
+    RoundRobin,
+    /// Whatever is waiting on the earliest connected edge that has something.
+    Priority
+}
+
+/// A connection between two nodes. Every output of `from` is sent down each of its
+/// outgoing edges.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub struct Edge {
+    pub from: NodeId,
+    pub to: NodeId,
+    /// Values `from` has put on the edge.
+    pub sent: u64,
+    /// Values `to` has taken off it.
+    pub received: u64
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Outcome {
+    /// Every machine has halted.
+    Halted,
+    /// Nothing can make progress: the machines still running all wait for input that
+    /// isn't coming.
+    Quiescent
+}
+
+/// A machine fault, with the node it happened in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeError {
+    pub node: NodeId,
+    pub error: IntcodeError
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "node {}: {}", self.node, self.error)
+    }
+}
+
+impl std::error::Error for NodeError {}
+
+struct Node<W: Word> {
+    machine: IntCodeMachine<W>,
+    merge: Merge,
+    incoming: Vec<EdgeId>,
+    outgoing: Vec<EdgeId>,
+    turn: usize
+}
+
+/// Machines wired together in an arbitrary directed graph.
+///
+/// Nodes take turns in the order they were added, each running until it halts or
+/// waits for input nobody has sent yet. Queued values are stamped in the order they
+/// were sent, so a run is fully deterministic.
+pub struct Circuit<W: Word = i64> {
+    nodes: Vec<Node<W>>,
+    edges: Vec<Edge>,
+    queues: Vec<VecDeque<(u64, W)>>,
+    sequence: u64
+}
+
+impl<W: Word> Default for Circuit<W> {
+    fn default() -> Self {
+        Self { nodes: vec![], edges: vec![], queues: vec![], sequence: 0 }
+    }
+}
+
+impl<W: Word> Circuit<W> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_machine(&mut self, machine: IntCodeMachine<W>) -> NodeId {
+        self.nodes.push(Node { machine, merge: Merge::default(), incoming: vec![], outgoing: vec![], turn: 0 });
+        self.nodes.len() - 1
+    }
+
+    pub fn connect(&mut self, from: NodeId, to: NodeId) -> EdgeId {
+        let edge = self.edges.len();
+
+        self.edges.push(Edge { from, to, sent: 0, received: 0 });
+        self.queues.push(VecDeque::new());
+        self.nodes[from].outgoing.push(edge);
+        self.nodes[to].incoming.push(edge);
+
+        edge
+    }
+
+    /// Queue `values` for `node` ahead of anything arriving over its edges.
+    pub fn seed(&mut self, node: NodeId, values: &[W]) -> Result<(), NodeError> {
+        self.nodes[node].machine.push_inputs(values).map_err(|error| NodeError { node, error })
+    }
+
+    pub fn set_merge(&mut self, node: NodeId, merge: Merge) {
+        self.nodes[node].merge = merge;
+    }
+
+    pub fn machine(&self, node: NodeId) -> &IntCodeMachine<W> {
+        &self.nodes[node].machine
+    }
+
+    pub fn machine_mut(&mut self, node: NodeId) -> &mut IntCodeMachine<W> {
+        &mut self.nodes[node].machine
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Values sent down `edge` that haven't been taken off it yet.
+    pub fn pending(&self, edge: EdgeId) -> usize {
+        self.queues[edge].len()
+    }
+
+    // The edge `node` takes its next input from, if there is anything for it.
+    fn next_edge(&self, node: NodeId) -> Option<EdgeId> {
+        let node = &self.nodes[node];
+        let mut waiting = node.incoming.iter().cloned().filter(|edge| !self.queues[*edge].is_empty());
+
+        match node.merge {
+            Merge::Arrival => waiting.min_by_key(|edge| self.queues[*edge][0].0),
+            Merge::RoundRobin => node.incoming.get(node.turn).cloned().filter(|edge| !self.queues[*edge].is_empty()),
+            Merge::Priority => waiting.next()
+        }
+    }
+
+    fn receive(&mut self, node: NodeId) -> Option<W> {
+        let edge = self.next_edge(node)?;
+        let (_, value) = self.queues[edge].pop_front()?;
+        let incoming = self.nodes[node].incoming.len();
+
+        self.edges[edge].received += 1;
+        self.nodes[node].turn = (self.nodes[node].turn + 1) % incoming;
+
+        Some(value)
+    }
+
+    fn send(&mut self, node: NodeId, value: W) {
+        for edge in self.nodes[node].outgoing.clone() {
+            self.queues[edge].push_back((self.sequence, value.clone()));
+            self.edges[edge].sent += 1;
+            self.sequence += 1;
+        }
+    }
+
+    // Run one node until it halts or starves. Returns whether it executed anything.
+    fn turn(&mut self, node: NodeId) -> Result<bool, IntcodeError> {
+        let before = self.nodes[node].machine.cycles();
+
+        loop {
+            match self.nodes[node].machine.state() {
+                MachineState::Halted => break,
+                MachineState::AwaitingInput => match self.receive(node) {
+                    Some(value) => self.nodes[node].machine.push_input(value)?,
+                    None => break
+                },
+                _ => {}
+            }
+
+            if let StopReason::Output(value) = self.nodes[node].machine.run_until_output()? {
+                self.send(node, value);
+            }
+        }
+
+        Ok(self.nodes[node].machine.cycles() != before)
+    }
+
+    /// Run until every machine has halted or none can make progress.
+    pub fn run(&mut self) -> Result<Outcome, NodeError> {
+        loop {
+            let mut progress = false;
+
+            for node in 0..self.nodes.len() {
+                progress |= self.turn(node).map_err(|error| NodeError { node, error })?;
+            }
+
+            if self.nodes.iter().all(|node| node.machine.state() == &MachineState::Halted) {
+                return Ok(Outcome::Halted);
+            }
+
+            if !progress {
+                return Ok(Outcome::Quiescent);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod circuit_tests {
+    use crate::int_code_computer::circuit::*;
+    use crate::int_code_computer::fixtures::FEEDBACK;
+
+    // Reads two values a and b and outputs a * 10 + b.
+    const PAIR : [i64; 17] = [3,15,3,16,1002,15,10,15,1,15,16,15,4,15,99,0,0];
+    const DOUBLE : [i64; 10] = [3,9,102,2,9,9,4,9,99,0];
+
+    fn node(circuit: &mut Circuit, program: &[i64]) -> NodeId {
+        circuit.add_machine(IntCodeMachine::new(program, None))
+    }
+
+    #[test]
+    fn fan_out_copies_outputs(){
+        let mut circuit = Circuit::new();
+        let source = node(&mut circuit, &[104,4,99]);
+        let left = node(&mut circuit, &DOUBLE);
+        let right = node(&mut circuit, &DOUBLE);
+
+        circuit.connect(source, left);
+        circuit.connect(source, right);
+
+        assert_eq!(circuit.run(), Ok(Outcome::Halted));
+        assert_eq!(circuit.machine(left).output, vec![8]);
+        assert_eq!(circuit.machine(right).output, vec![8]);
+        assert!(circuit.edges().iter().all(|edge| edge.sent == 1 && edge.received == 1));
+    }
+
+    #[test]
+    fn fan_in_merge_policies(){
+        for (merge, expected) in [(Merge::Arrival, 57), (Merge::RoundRobin, 75), (Merge::Priority, 78)].iter() {
+            let mut circuit = Circuit::new();
+            let sink = node(&mut circuit, &PAIR);
+            let late = node(&mut circuit, &[104,5,99]);
+            let early = node(&mut circuit, &[104,7,104,8,99]);
+
+            // `late` runs before `early`, but is connected after it.
+            circuit.connect(early, sink);
+            circuit.connect(late, sink);
+            circuit.set_merge(sink, *merge);
+
+            assert_eq!(circuit.run(), Ok(Outcome::Halted));
+            assert_eq!(circuit.machine(sink).output, vec![*expected]);
+        }
+    }
+
+    #[test]
+    fn seeds_come_first_and_starved_nodes_quiesce(){
+        let mut circuit = Circuit::new();
+        let source = node(&mut circuit, &[104,3,99]);
+        let sink = node(&mut circuit, &PAIR);
+        let idle = node(&mut circuit, &DOUBLE);
+
+        circuit.connect(source, sink);
+        circuit.seed(sink, &[9]).unwrap();
+
+        assert_eq!(circuit.run(), Ok(Outcome::Quiescent));
+        assert_eq!(circuit.machine(sink).output, vec![93]);
+        assert_eq!(circuit.machine(idle).state(), &MachineState::AwaitingInput);
+    }
+
+    #[test]
+    fn reports_faulting_node(){
+        let mut circuit = Circuit::new();
+        let source = node(&mut circuit, &[104,1,99]);
+        let broken = node(&mut circuit, &[3,0,98]);
+
+        circuit.connect(source, broken);
+
+        assert_eq!(
+            circuit.run(),
+            Err(NodeError { node : broken, error : IntcodeError::InvalidOpcode { pc : 2, instruction : 98 } })
+        );
+    }
+
+    #[test]
+    fn feedback_ring(){
+        let mut circuit = Circuit::new();
+        let amps : Vec<NodeId> = [9, 8, 7, 6, 5].iter().map(|phase| {
+            let amp = node(&mut circuit, &FEEDBACK);
+
+            circuit.seed(amp, &[*phase]).unwrap();
+            amp
+        }).collect();
+
+        for index in 0..amps.len() {
+            circuit.connect(amps[index], amps[(index + 1) % amps.len()]);
+        }
+
+        circuit.seed(amps[0], &[0]).unwrap();
+
+        assert_eq!(circuit.run(), Ok(Outcome::Halted));
+        assert_eq!(circuit.machine(amps[4]).output.last(), Some(&139629729));
+        assert_eq!(circuit.edges()[4].sent, 5);
+        assert_eq!(circuit.pending(4), 1);
+    }
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use crate::int_code_computer::word::Word;
+
+/// A write into a cell that had already been executed as an instruction or operand.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct CodeWrite<W: Word = i64> {
+    pub pc: usize,
+    pub address: usize,
+    pub old: W,
+    pub new: W
+}
+
+impl<W: Word> fmt::Display for CodeWrite<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "pc {} wrote [{}]: {} -> {}", self.pc, self.address, self.old, self.new)
+    }
+}
+
+/// A run of adjacent self-modified cells, `start..end`.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Region {
+    pub start: usize,
+    pub end: usize,
+    pub writes: usize
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}: {} writes", self.start, self.end, self.writes)
+    }
+}
+
+/// Tracks which cells have been executed and reports writes that land on them.
+#[derive(PartialEq, Clone, Debug)]
+pub struct CodeMonitor<W: Word = i64> {
+    executed: BTreeSet<usize>,
+    events: Vec<CodeWrite<W>>
+}
+
+// Derived `Default` would needlessly require `W: Default`.
+impl<W: Word> Default for CodeMonitor<W> {
+    fn default() -> Self {
+        Self { executed: BTreeSet::new(), events: vec![] }
+    }
+}
+
+impl<W: Word> CodeMonitor<W> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Every self-modifying write so far, in execution order.
+    pub fn events(&self) -> &[CodeWrite<W>] {
+        &self.events
+    }
+
+    pub fn is_executed(&self, address: usize) -> bool {
+        self.executed.contains(&address)
+    }
+
+    // The instruction at `pc` covering `size` cells is marked first, so an instruction
+    // overwriting one of its own operands is reported too.
+    pub(crate) fn record(&mut self, pc: usize, size: usize, writes: Vec<(usize, W, W)>) {
+        self.executed.extend(pc..pc + size);
+
+        for (address, old, new) in writes {
+            if self.executed.contains(&address) {
+                self.events.push(CodeWrite { pc, address, old, new });
+            }
+        }
+    }
+
+    /// Self-modified cells merged into contiguous regions, in address order.
+    pub fn regions(&self) -> Vec<Region> {
+        let addresses : BTreeSet<usize> = self.events.iter().map(|event| event.address).collect();
+        let mut regions : Vec<Region> = vec![];
+
+        for address in addresses {
+            let writes = self.events.iter().filter(|event| event.address == address).count();
+
+            match regions.last_mut() {
+                Some(region) if region.end == address => {
+                    region.end += 1;
+                    region.writes += writes;
+                },
+                _ => regions.push(Region { start: address, end: address + 1, writes })
+            }
+        }
+
+        regions
+    }
+}
+
+#[cfg(test)]
+mod code_monitor_tests {
+    use crate::int_code_computer::code_monitor::*;
+    use crate::int_code_computer::IntCodeMachine;
+
+    #[test]
+    fn reports_writes_to_executed_cells(){
+        // Overwrites its own first instruction, then patches the operand of the ADD at 4
+        // after it has run once.
+        let program = vec![1,0,0,0, 1101,1,1,12, 1101,7,0,5, 0,0];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        machine.set_code_monitoring(true);
+        machine.step().unwrap();
+        machine.step().unwrap();
+        machine.step().unwrap();
+
+        let monitor = machine.code_monitor().unwrap();
+
+        assert_eq!(monitor.events(), &[
+            CodeWrite { pc: 0, address: 0, old: 1, new: 2 },
+            CodeWrite { pc: 8, address: 5, old: 1, new: 7 }
+        ]);
+        assert!(monitor.is_executed(11));
+        assert!(!monitor.is_executed(12));
+        assert_eq!(monitor.regions(), vec![Region { start: 0, end: 1, writes: 1 }, Region { start: 5, end: 6, writes: 1 }]);
+    }
+
+    #[test]
+    fn data_writes_are_not_reported(){
+        let program = vec![1101,1,1,5,99,0];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        machine.set_code_monitoring(true);
+        machine.run().unwrap();
+
+        assert!(machine.code_monitor().unwrap().events().is_empty());
+    }
+
+    #[test]
+    fn merges_adjacent_regions(){
+        let mut monitor = CodeMonitor::new();
+
+        monitor.record(0, 4, vec![(2, 0, 1)]);
+        monitor.record(4, 4, vec![(3, 0, 1)]);
+        monitor.record(4, 4, vec![(3, 1, 2)]);
+        monitor.record(8, 4, vec![(6, 0, 1)]);
+
+        assert_eq!(monitor.regions(), vec![Region { start: 2, end: 4, writes: 3 }, Region { start: 6, end: 7, writes: 1 }]);
+        assert_eq!(monitor.events()[1].to_string(), "pc 4 wrote [3]: 0 -> 1");
+    }
+}
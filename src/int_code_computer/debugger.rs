@@ -0,0 +1,244 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt;
+use crate::int_code_computer::{MachineState, Opcode, OpcodeArg};
+use crate::int_code_computer::word::Word;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Access {
+    Read,
+    Write
+}
+
+/// Why a run stopped early. Breakpoints and opcode breaks fire before the instruction
+/// executes; watchpoints and I/O breaks fire after it, with `pc` naming the instruction.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum BreakReason<W: Word = i64> {
+    Breakpoint { pc: usize },
+    Opcode { pc: usize, opcode: Opcode },
+    Watchpoint { pc: usize, address: usize, access: Access },
+    Input { pc: usize, value: W },
+    Output { pc: usize, value: W },
+    /// A custom instruction read input or produced output through its `Host`.
+    HostIo { pc: usize, opcode: Opcode }
+}
+
+/// The conditions a machine checks while running. Single steps ignore them.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Breakpoints {
+    addresses: BTreeSet<usize>,
+    reads: BTreeSet<usize>,
+    writes: BTreeSet<usize>,
+    opcodes: HashSet<Opcode>,
+    io: bool
+}
+
+impl Breakpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns false if there already was a breakpoint at `address`.
+    pub fn add(&mut self, address: usize) -> bool {
+        self.addresses.insert(address)
+    }
+
+    pub fn remove(&mut self, address: usize) -> bool {
+        self.addresses.remove(&address)
+    }
+
+    pub fn addresses(&self) -> impl Iterator<Item = &usize> {
+        self.addresses.iter()
+    }
+
+    pub fn watch(&mut self, address: usize, access: Access) {
+        match access {
+            Access::Read => self.reads.insert(address),
+            Access::Write => self.writes.insert(address)
+        };
+    }
+
+    /// Stop watching `address` for both reads and writes.
+    pub fn unwatch(&mut self, address: usize) {
+        self.reads.remove(&address);
+        self.writes.remove(&address);
+    }
+
+    pub fn watches(&self) -> impl Iterator<Item = (usize, Access)> + '_ {
+        self.reads.iter().map(|address| (*address, Access::Read))
+            .chain(self.writes.iter().map(|address| (*address, Access::Write)))
+    }
+
+    pub fn break_on(&mut self, opcode: Opcode) {
+        self.opcodes.insert(opcode);
+    }
+
+    pub fn clear_break_on(&mut self, opcode: Opcode) {
+        self.opcodes.remove(&opcode);
+    }
+
+    /// Break after every completed `INPUT` and `OUTPUT`, and every custom instruction
+    /// that did I/O.
+    pub fn set_break_on_io(&mut self, enabled: bool) {
+        self.io = enabled;
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty() && self.reads.is_empty() && self.writes.is_empty() && self.opcodes.is_empty() && !self.io
+    }
+
+    pub(crate) fn before<W: Word>(&self, pc: usize, opcode: Option<Opcode>) -> Option<BreakReason<W>> {
+        if self.addresses.contains(&pc) {
+            return Some(BreakReason::Breakpoint { pc });
+        }
+
+        opcode.filter(|opcode| self.opcodes.contains(opcode))
+            .map(|opcode| BreakReason::Opcode { pc, opcode })
+    }
+
+    // `input` is the value an INPUT instruction stored, since its args only show what
+    // was in the cell beforehand. `host_io` says a custom instruction did I/O.
+    pub(crate) fn after<W: Word>(&self, pc: usize, opcode: Opcode, args: &[OpcodeArg<W>], input: Option<W>, host_io: bool) -> Option<BreakReason<W>> {
+        let watched = memory_accesses(&opcode, args).into_iter().find(|(address, access)| match access {
+            Access::Read => self.reads.contains(address),
+            Access::Write => self.writes.contains(address)
+        });
+
+        if let Some((address, access)) = watched {
+            return Some(BreakReason::Watchpoint { pc, address, access });
+        }
+
+        match (self.io, opcode) {
+            (true, Opcode::Input) => input.map(|value| BreakReason::Input { pc, value }),
+            (true, Opcode::Output) => Some(BreakReason::Output { pc, value: args[0].value.clone() }),
+            (true, Opcode::Custom(_)) if host_io => Some(BreakReason::HostIo { pc, opcode }),
+            _ => None
+        }
+    }
+}
+
+/// Memory cells an executed instruction touched, in operand order. Immediate operands
+/// don't touch memory; the write operand is only a write.
+pub fn memory_accesses<W: Word>(opcode: &Opcode, args: &[OpcodeArg<W>]) -> Vec<(usize, Access)> {
+    args.iter().enumerate()
+        .filter(|(_, arg)| arg.parameter_mode != 1 && arg.address >= 0)
+        .map(|(index, arg)| match opcode.is_write_operand(index) {
+            true => (arg.address as usize, Access::Write),
+            false => (arg.address as usize, Access::Read)
+        })
+        .collect()
+}
+
+/// One executed (or, for a blocked `INPUT`, attempted) instruction.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Step<W: Word = i64> {
+    pub address: usize,
+    pub opcode: Opcode,
+    pub args: Vec<OpcodeArg<W>>,
+    pub state: MachineState
+}
+
+impl<W: Word> fmt::Display for Step<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.address, self.opcode.disassemble(&self.args))
+    }
+}
+
+#[cfg(test)]
+mod debugger_tests {
+    use crate::int_code_computer::debugger::*;
+    use crate::int_code_computer::{IntCodeMachine, StopReason};
+    use crate::int_code_computer::fixtures::COUNTDOWN;
+
+    #[test]
+    fn step_resolves_operands(){
+        let mut machine = IntCodeMachine::new(&COUNTDOWN, None);
+
+        machine.step().unwrap();
+
+        let step = machine.step().unwrap();
+
+        assert_eq!(step.address, 4);
+        assert_eq!(step.opcode, Opcode::Add);
+        assert_eq!(step.args.iter().map(|arg| arg.value()).collect::<Vec<i64>>(), vec![0, 3, 0]);
+        assert_eq!(step.args[2].address(), 20);
+        assert_eq!(step.state, MachineState::Running);
+        assert_eq!(machine.memory.read(20), 3);
+    }
+
+    #[test]
+    fn breakpoint_stops_before_and_resumes(){
+        let mut machine = IntCodeMachine::new(&COUNTDOWN, None);
+
+        machine.breakpoints_mut().add(8);
+
+        assert_eq!(machine.run_until_halt(), Ok(StopReason::Break(BreakReason::Breakpoint { pc : 8 })));
+        assert_eq!(machine.memory.read(21), 3);
+        assert_eq!(machine.run(), Ok(MachineState::Running));
+        assert_eq!(machine.last_break(), Some(BreakReason::Breakpoint { pc : 8 }));
+        assert_eq!(machine.memory.read(21), 2);
+
+        machine.breakpoints_mut().clear();
+
+        assert_eq!(machine.run(), Ok(MachineState::Halted));
+        assert_eq!(machine.memory.read(20), 6);
+    }
+
+    #[test]
+    fn watchpoints_stop_after_access(){
+        let mut machine = IntCodeMachine::new(&COUNTDOWN, None);
+
+        machine.breakpoints_mut().watch(20, Access::Write);
+
+        assert_eq!(
+            machine.run_until_input(),
+            Ok(StopReason::Break(BreakReason::Watchpoint { pc : 4, address : 20, access : Access::Write }))
+        );
+        assert_eq!(machine.program_counter(), 8);
+
+        machine.breakpoints_mut().unwatch(20);
+        machine.breakpoints_mut().watch(21, Access::Read);
+
+        assert_eq!(
+            machine.run_until_input(),
+            Ok(StopReason::Break(BreakReason::Watchpoint { pc : 8, address : 21, access : Access::Read }))
+        );
+    }
+
+    #[test]
+    fn break_on_opcode_and_io(){
+        let program = vec![3,9,1002,9,2,9,4,9,99,0];
+        let mut machine = IntCodeMachine::new(&program, Some(&vec![21]));
+
+        machine.breakpoints_mut().break_on(Opcode::Mult);
+        machine.breakpoints_mut().set_break_on_io(true);
+
+        assert_eq!(machine.run_until_output(), Ok(StopReason::Break(BreakReason::Input { pc : 0, value : 21 })));
+        assert_eq!(machine.run_until_output(), Ok(StopReason::Break(BreakReason::Opcode { pc : 2, opcode : Opcode::Mult })));
+        assert_eq!(machine.run_until_output(), Ok(StopReason::Break(BreakReason::Output { pc : 6, value : 42 })));
+        assert_eq!(machine.run_until_output(), Ok(StopReason::Halted));
+    }
+
+    #[test]
+    fn break_on_host_io(){
+        // `ECHO` reads a value and outputs it; `NOP` touches nothing.
+        let mut machine = IntCodeMachine::new(&[30, 31, 30, 99], Some(&vec![5, 6]));
+
+        machine.registry_mut().register(30, "ECHO", 0, &[], |host| match host.input()? {
+            Some(value) => host.output(value),
+            None => Ok(())
+        }).unwrap();
+        machine.registry_mut().register(31, "NOP", 0, &[], |_| Ok(())).unwrap();
+        machine.breakpoints_mut().set_break_on_io(true);
+
+        let echo = machine.registry().decode(30).unwrap().0;
+
+        assert_eq!(machine.run_until_halt(), Ok(StopReason::Break(BreakReason::HostIo { pc : 0, opcode : echo })));
+        assert_eq!(machine.run_until_halt(), Ok(StopReason::Break(BreakReason::HostIo { pc : 2, opcode : echo })));
+        assert_eq!(machine.run_until_halt(), Ok(StopReason::Halted));
+        assert_eq!(machine.output, vec![5, 6]);
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use crate::int_code_computer::Opcode;
+use crate::int_code_computer::registry::Registry;
+use crate::int_code_computer::word::Word;
+
+/// A raw operand as it appears in the program: its parameter mode and the word stored
+/// in the instruction stream. Nothing is resolved, so this is safe to build without
+/// running anything.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Operand<W: Word = i64> {
+    pub mode: i64,
+    pub raw: W
+}
+
+impl<W: Word> fmt::Display for Operand<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mode {
+            1 => write!(f, "#{}", self.raw),
+            2 if self.raw < W::from_i64(0) => write!(f, "[rb{}]", self.raw),
+            2 => write!(f, "[rb+{}]", self.raw),
+            _ => write!(f, "[{}]", self.raw)
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum ListingEntry<W: Word = i64> {
+    Instruction { address: usize, opcode: Opcode, operands: Vec<Operand<W>> },
+    Data { address: usize, values: Vec<W> }
+}
+
+impl<W: Word> ListingEntry<W> {
+    pub fn address(&self) -> usize {
+        match *self {
+            ListingEntry::Instruction { address, .. } => address,
+            ListingEntry::Data { address, .. } => address
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            ListingEntry::Instruction { opcode, .. } => opcode.get_size(),
+            ListingEntry::Data { values, .. } => values.len()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<W: Word> fmt::Display for ListingEntry<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, operands) = match self {
+            ListingEntry::Instruction { opcode, operands, .. } => (
+                opcode.to_string(),
+                operands.iter().map(|operand| operand.to_string()).collect::<Vec<String>>()
+            ),
+            ListingEntry::Data { values, .. } => (
+                String::from("DATA"),
+                values.iter().map(|value| value.to_string()).collect::<Vec<String>>()
+            )
+        };
+
+        if operands.is_empty() {
+            return write!(f, "{}", name);
+        }
+
+        write!(f, "{} {}", name, operands.join(", "))
+    }
+}
+
+/// An address-ordered view of a program image. Every cell belongs to exactly one entry.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Listing<W: Word = i64> {
+    pub entries: Vec<ListingEntry<W>>
+}
+
+impl<W: Word> Listing<W> {
+    /// The listing without addresses, in the syntax the assembler reads.
+    pub fn to_source(&self) -> String {
+        self.entries.iter().map(|entry| format!("{}\n", entry)).collect()
+    }
+
+    pub fn entry_at(&self, address: usize) -> Option<&ListingEntry<W>> {
+        self.entries.iter().find(|entry| entry.address() <= address && address < entry.address() + entry.len())
+    }
+}
+
+impl<W: Word> fmt::Display for Listing<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.entries.iter() {
+            writeln!(f, "{:>5}: {}", entry.address(), entry)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Decode the instruction at `address` without executing it. Returns None if the word
+/// isn't a valid instruction or the operands run off the end of the image.
+pub fn decode<W: Word>(program: &[W], address: usize) -> Option<(Opcode, Vec<Operand<W>>)> {
+    decode_with(program, address, &Registry::new())
+}
+
+/// `decode`, also recognising the opcodes in `registry`.
+pub fn decode_with<W: Word>(program: &[W], address: usize, registry: &Registry<W>) -> Option<(Opcode, Vec<Operand<W>>)> {
+    let (opcode, parameter_mode) = registry.decode(program.get(address)?.clone()).ok()?;
+    let mut mode = parameter_mode;
+    let mut operands = vec![];
+
+    for arg_address in address+1..address+opcode.get_size() {
+        if mode % 10 > 2 {
+            return None;
+        }
+
+        operands.push(Operand { mode: mode % 10, raw: program.get(arg_address)?.clone() });
+        mode /= 10;
+    }
+
+    Some((opcode, operands))
+}
+
+// Addresses control can move to after the instruction at `address`. Only immediate
+// jump targets are followed; anything computed at runtime is invisible to a static sweep.
+fn successors<W: Word>(address: usize, opcode: &Opcode, operands: &[Operand<W>]) -> Vec<usize> {
+    let fall_through = address + opcode.get_size();
+
+    let (jump_when_nonzero, condition, target) = match opcode {
+        Opcode::ProgramEnd => return vec![],
+        Opcode::JumpIfTrue => (true, &operands[0], &operands[1]),
+        Opcode::JumpIfFalse => (false, &operands[0], &operands[1]),
+        _ => return vec![fall_through]
+    };
+
+    let mut next = vec![];
+    let taken = match condition.mode {
+        1 => Some(condition.raw.is_zero() != jump_when_nonzero),
+        _ => None
+    };
+
+    if taken != Some(true) {
+        next.push(fall_through);
+    }
+
+    if let (true, 1, Some(target)) = (taken != Some(false), target.mode, target.raw.to_i64()) {
+        if target >= 0 {
+            next.push(target as usize);
+        }
+    }
+
+    next
+}
+
+/// Statically sweep everything reachable from address 0 and list it in address order.
+/// Cells that no reachable instruction covers are reported as data.
+pub fn disassemble<W: Word>(program: &[W]) -> Listing<W> {
+    disassemble_with(program, &Registry::new())
+}
+
+/// `disassemble`, also recognising the opcodes in `registry`.
+pub fn disassemble_with<W: Word>(program: &[W], registry: &Registry<W>) -> Listing<W> {
+    let mut instructions : BTreeMap<usize, (Opcode, Vec<Operand<W>>)> = BTreeMap::new();
+    let mut visited : HashSet<usize> = HashSet::new();
+    let mut pending = vec![0];
+
+    while let Some(address) = pending.pop() {
+        if !visited.insert(address) {
+            continue;
+        }
+
+        if let Some((opcode, operands)) = decode_with(program, address, registry) {
+            pending.extend(successors(address, &opcode, &operands));
+            instructions.insert(address, (opcode, operands));
+        }
+    }
+
+    let mut entries = vec![];
+    let mut address = 0;
+
+    // Jumps into the middle of an earlier instruction are dropped in favour of the
+    // instruction that covers them, so the listing never overlaps.
+    while address < program.len() {
+        if let Some((opcode, operands)) = instructions.get(&address) {
+            entries.push(ListingEntry::Instruction { address, opcode: *opcode, operands: operands.clone() });
+            address += opcode.get_size();
+            continue;
+        }
+
+        let start = address;
+
+        while address < program.len() && !instructions.contains_key(&address) {
+            address += 1;
+        }
+
+        entries.push(ListingEntry::Data { address: start, values: program[start..address].to_vec() });
+    }
+
+    Listing { entries }
+}
+
+#[cfg(test)]
+mod disassembler_tests {
+    use crate::int_code_computer::disassembler::*;
+
+    #[test]
+    fn straight_line_program(){
+        let program = vec![3, 1, 4, 1, 1101, 2, 2, 1, 1102, 5, 5, 2, 99];
+
+        assert_eq!(
+            disassemble(&program).to_source(),
+"INPUT [1]
+OUTPUT [1]
+ADD #2, #2, [1]
+MULT #5, #5, [2]
+HALT
+"
+        )
+    }
+
+    #[test]
+    fn unreachable_cells_are_data(){
+        let program = vec![1105, 1, 5, 42, 42, 99, 7];
+
+        assert_eq!(
+            disassemble(&program).to_string(),
+"    0: JUMP_TRUE #1, #5
+    3: DATA 42, 42
+    5: HALT
+    6: DATA 7
+"
+        )
+    }
+
+    #[test]
+    fn follows_both_branches(){
+        let program = vec![1005, 12, 9, 104, 1, 1105, 1, 9, 99, 204, -3, 99, 0];
+        let listing = disassemble(&program);
+
+        assert_eq!(
+            listing.to_source(),
+"JUMP_TRUE [12], #9
+OUTPUT #1
+JUMP_TRUE #1, #9
+DATA 99
+OUTPUT [rb-3]
+HALT
+DATA 0
+"
+        );
+        assert_eq!(listing.entry_at(10).unwrap().address(), 9);
+    }
+
+    #[test]
+    fn invalid_words_are_data(){
+        let program = vec![1006, 5, 4, 99, 42, 0];
+
+        assert_eq!(
+            disassemble(&program).to_source(),
+"JUMP_FALSE [5], #4
+HALT
+DATA 42, 0
+"
+        )
+    }
+}
@@ -0,0 +1,303 @@
+use crate::int_code_computer::{IntCodeMachine, IntcodeError, MachineState, Opcode, OpcodeArg, StopReason};
+use crate::int_code_computer::memory::Memory;
+use crate::int_code_computer::word::Word;
+
+/// Which loop executes instructions.
+///
+/// `Predecoded` keeps a cache of decoded instructions and executes them without
+/// allocating. It only serves `run` and the `run_until_*` drivers while nothing is
+/// observing individual instructions (breakpoints, profiling, recording, loop
+/// detection, code monitoring, debug output) and no custom opcodes are registered;
+/// otherwise the machine quietly uses the interpreter.
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Engine {
+    Interpreter,
+    Predecoded
+}
+
+/// An instruction word split into its opcode, raw operands and parameter modes.
+/// Modes are kept as found and only validated when an operand is resolved, so errors
+/// come out in the same order as the interpreter's.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub struct Decoded<W: Word = i64> {
+    pub opcode: Opcode,
+    pub arity: usize,
+    pub modes: [i64; 3],
+    pub operands: [W; 3]
+}
+
+impl<W: Word> Decoded<W> {
+    pub fn decode(memory: &Memory<W>, pc: usize) -> Result<Self, IntcodeError> {
+        let (opcode, parameter_mode) = Opcode::new(memory.read(pc)).map_err(|err| err.at(pc))?;
+        let zero = W::from_i64(0);
+        let mut decoded = Decoded { opcode, arity: opcode.get_size() - 1, modes: [0; 3], operands: [zero.clone(), zero.clone(), zero] };
+        let mut mode = parameter_mode;
+
+        for index in 0..decoded.arity {
+            decoded.modes[index] = mode % 10;
+            decoded.operands[index] = memory.read(pc + 1 + index);
+            mode /= 10;
+        }
+
+        Ok(decoded)
+    }
+}
+
+/// Decoded instructions for the program image, indexed by address.
+#[derive(Clone, Debug)]
+pub struct DecodeCache<W: Word = i64> {
+    entries: Vec<Option<Decoded<W>>>
+}
+
+impl<W: Word> DecodeCache<W> {
+    pub fn new(len: usize) -> Self {
+        Self { entries: vec![None; len] }
+    }
+
+    pub fn get(&self, address: usize) -> Option<Decoded<W>> {
+        self.entries.get(address).cloned().flatten()
+    }
+
+    /// Instructions whose operands run past the image aren't cached: those cells are
+    /// paged memory, and writes there don't invalidate anything.
+    pub fn insert(&mut self, address: usize, decoded: Decoded<W>) {
+        if address + decoded.arity >= self.entries.len() {
+            return;
+        }
+
+        if let Some(entry) = self.entries.get_mut(address) {
+            *entry = Some(decoded);
+        }
+    }
+
+    /// Drop every cached instruction that covers `address`. Instructions are at most
+    /// four words long, so only the three cells before it can start one.
+    pub fn invalidate(&mut self, address: usize) {
+        let end = self.entries.len().min(address + 1);
+
+        for entry in self.entries[address.saturating_sub(3).min(end)..end].iter_mut() {
+            *entry = None;
+        }
+    }
+}
+
+impl<W: Word> IntCodeMachine<W> {
+    pub(crate) fn can_predecode(&self) -> bool {
+        self.engine == Engine::Predecoded
+            && !self.output_dasm
+            && self.profile.is_none()
+            && self.history.is_none()
+            && self.seen_states.is_none()
+            && self.code_monitor.is_none()
+            && self.breakpoints.is_empty()
+            && self.registry.is_empty()
+    }
+
+    fn resolve(&self, mode: i64, operand: &W) -> Result<OpcodeArg<W>, IntcodeError> {
+        match mode {
+            0 => {
+                let address = self.to_address(operand)?;
+
+                Ok(OpcodeArg::new(mode, self.memory.read(address), address as i64))
+            },
+            1 => Ok(OpcodeArg::new(mode, operand.clone(), operand.saturating_i64())),
+            2 => {
+                let address = self.relative_address(operand)?;
+
+                Ok(OpcodeArg::new(mode, self.memory.read(self.address_of(address)?), address))
+            },
+            _ => Err(IntcodeError::InvalidParameterMode {
+                pc: self.program_counter,
+                instruction: self.memory.read(self.program_counter).saturating_i64(),
+                mode
+            })
+        }
+    }
+
+    // Same effects as `run_cycle`, with operands resolved into a fixed-size array.
+    // Returns the value written by an OUTPUT.
+    fn predecoded_cycle(&mut self) -> Result<Option<W>, IntcodeError> {
+        let pc = self.program_counter;
+        let decoded = self.memory.decode_cached(pc)?;
+        let unused = || OpcodeArg::new(0, W::from_i64(0), 0);
+        let mut args = [unused(), unused(), unused()];
+
+        for (index, arg) in args.iter_mut().enumerate().take(decoded.arity) {
+            *arg = self.resolve(decoded.modes[index], &decoded.operands[index])?;
+        }
+
+        let next = pc + decoded.arity + 1;
+
+        match decoded.opcode {
+            Opcode::Add => self.write(&args[2], self.checked(args[0].value.add(&args[1].value, self.arithmetic))?)?,
+            Opcode::Mult => self.write(&args[2], self.checked(args[0].value.mul(&args[1].value, self.arithmetic))?)?,
+            Opcode::LessThan => self.write(&args[2], W::from_i64((args[0].value < args[1].value) as i64))?,
+            Opcode::Equals => self.write(&args[2], W::from_i64((args[0].value == args[1].value) as i64))?,
+            Opcode::Input => match self.get_input()? {
+                Some(value) => self.write(&args[0], value)?,
+                None => {
+                    self.state = MachineState::AwaitingInput;
+                    return Ok(None);
+                }
+            },
+            Opcode::Output => {
+                self.put_output(args[0].value.clone())?;
+                self.program_counter = next;
+                return Ok(Some(args[0].value.clone()));
+            },
+            Opcode::JumpIfTrue | Opcode::JumpIfFalse => {
+                if args[0].value.is_zero() != (decoded.opcode == Opcode::JumpIfTrue) {
+                    self.program_counter = self.to_address(&args[1].value)?;
+                    return Ok(None);
+                }
+            },
+            Opcode::RBO => self.relative_base_offset = self.adjusted_relative_base(&args[0].value)?,
+            Opcode::ProgramEnd => {
+                self.state = MachineState::Halted;
+                return Ok(None);
+            },
+            Opcode::Custom(_) => unreachable!("custom opcodes are never predecoded")
+        }
+
+        self.program_counter = next;
+
+        Ok(None)
+    }
+
+    /// The predecoded counterpart of the interpreter's driver loop, with the same
+    /// budgets, cycle counting and state transitions.
+    pub(crate) fn run_predecoded(&mut self, stop_on_output: bool) -> Result<StopReason<W>, IntcodeError> {
+        let mut executed = 0;
+
+        self.last_break = None;
+        self.memory.enable_decode_cache();
+
+        loop {
+            if self.cycle_budget.is_some_and(|budget| executed >= budget) {
+                return Err(self.budget_exhausted());
+            }
+
+            self.resume()?;
+
+            if self.total_budget.is_some_and(|budget| self.cycles >= budget) {
+                return Err(self.budget_exhausted());
+            }
+
+            let output = self.predecoded_cycle().inspect_err(|err| {
+                self.state = MachineState::Faulted(err.clone());
+            })?;
+            executed += 1;
+
+            if self.state != MachineState::AwaitingInput {
+                self.cycles += 1;
+                self.resume_past = None;
+            }
+
+            if let Some(reason) = self.stop_reason() {
+                return Ok(reason);
+            }
+
+            if let (true, Some(value)) = (stop_on_output, output) {
+                return Ok(StopReason::Output(value));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod engine_tests {
+    use crate::int_code_computer::engine::*;
+
+    fn both(program: &[i64], input: &[i64]) -> (IntCodeMachine, IntCodeMachine) {
+        let mut interpreted = IntCodeMachine::new(program, Some(&input.to_vec()));
+        let mut predecoded = IntCodeMachine::new(program, Some(&input.to_vec()));
+
+        predecoded.set_engine(Engine::Predecoded);
+
+        let interpreted_result = interpreted.run();
+        let predecoded_result = predecoded.run();
+
+        assert_eq!(interpreted_result, predecoded_result);
+        assert_eq!(interpreted.snapshot(), predecoded.snapshot());
+        assert_eq!(interpreted.cycles(), predecoded.cycles());
+
+        (interpreted, predecoded)
+    }
+
+    #[test]
+    fn matches_interpreter_on_day_9(){
+        let program = IntCodeMachine::read_file_into_program("day-9-part-1-input").unwrap();
+        let (_, predecoded) = both(&program, &[1]);
+
+        assert_eq!(predecoded.output, vec![2494485073]);
+    }
+
+    #[test]
+    fn matches_interpreter_on_faults(){
+        both(&[1101,1,1,-1,99], &[]);
+        both(&[204,-5,99], &[]);
+        both(&[1,0,0,0,98], &[]);
+        both(&[3,5,1104,1,99], &[]);
+        both(&[3,0,4,0,99], &[]);
+    }
+
+    #[test]
+    fn invalidates_overwritten_code(){
+        // Loops three times, patching the first ADD from `+ 1` into `+ 10` after it runs.
+        let program = vec![1001,20,1,20, 1101,10,0,2, 1001,21,-1,21, 1005,21,0, 99, 0,0,0,0, 0,3];
+        let (_, predecoded) = both(&program, &[]);
+
+        assert_eq!(predecoded.memory.read(20), 21);
+    }
+
+    #[test]
+    fn rereads_operands_past_the_image(){
+        // The last image instruction, OUTPUT [25], has its operand in paged memory; the
+        // loop bumps that operand so each pass prints a different cell.
+        let program = vec![1001,25,1,25, 1101,0,1106,26, 1101,0,15,28, 1105,1,24, 1001,23,-1,23, 1005,23,0, 99, 3, 104];
+        let (interpreted, predecoded) = both(&program, &[]);
+
+        assert_eq!(interpreted.output, vec![1,2,3]);
+        assert_eq!(predecoded.output, vec![1,2,3]);
+    }
+
+    #[test]
+    fn skips_instructions_running_past_the_image(){
+        let mut cache = DecodeCache::new(4);
+        let decoded = Decoded { opcode: Opcode::Add, arity: 3, modes: [0; 3], operands: [0; 3] };
+
+        cache.insert(1, decoded);
+        assert_eq!(cache.get(1), None);
+
+        cache.insert(0, decoded);
+        assert_eq!(cache.get(0), Some(decoded));
+    }
+
+    #[test]
+    fn cache_invalidation_covers_instruction(){
+        let mut cache = DecodeCache::new(8);
+        let decoded = Decoded { opcode: Opcode::Add, arity: 3, modes: [0; 3], operands: [0; 3] };
+
+        cache.insert(2, decoded);
+        cache.invalidate(6);
+        assert_eq!(cache.get(2), Some(decoded));
+
+        cache.invalidate(5);
+        assert_eq!(cache.get(2), None);
+
+        cache.invalidate(100);
+    }
+
+    #[test]
+    fn falls_back_while_observed(){
+        let mut machine = IntCodeMachine::new(&[1101,1,1,5,99,0], None);
+
+        machine.set_engine(Engine::Predecoded);
+        assert!(machine.can_predecode());
+
+        machine.set_profiling(true);
+        assert!(!machine.can_predecode());
+        assert_eq!(machine.run(), Ok(MachineState::Halted));
+        assert_eq!(machine.profile().unwrap().total(), 2);
+    }
+}
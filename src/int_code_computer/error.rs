@@ -0,0 +1,97 @@
+use std::fmt;
+use crate::int_code_computer::state::MachineState;
+
+/// Everything that can go wrong while decoding or executing an Intcode program.
+///
+/// Each variant carries the program counter of the faulting instruction so callers can
+/// report (or match on) the exact failure instead of a bare message. Words wider than an
+/// i64 are saturated, except for out of range addresses, which keep their exact digits.
+#[derive(Clone, Debug, PartialEq)]
+pub enum IntcodeError {
+    InvalidOpcode { pc: usize, instruction: i64 },
+    InvalidParameterMode { pc: usize, instruction: i64, mode: i64 },
+    NegativeAddress { pc: usize, address: i64 },
+    WriteInImmediateMode { pc: usize, instruction: i64 },
+    InputExhausted { pc: usize },
+    IoFailure { pc: usize, message: String },
+    IllegalTransition { pc: usize, from: Box<MachineState>, to: Box<MachineState> },
+    BudgetExhausted { pc: usize, cycles: u64 },
+    InfiniteLoop { pc: usize, cycles: u64 },
+    ArithmeticOverflow { pc: usize, instruction: i64 },
+    AddressOutOfRange { pc: usize, address: String },
+    /// A custom instruction wrote through an operand it didn't declare as a write target.
+    UndeclaredWrite { pc: usize, opcode: i64, operand: usize }
+}
+
+impl IntcodeError {
+    pub fn pc(&self) -> usize {
+        match *self {
+            IntcodeError::InvalidOpcode { pc, .. } => pc,
+            IntcodeError::InvalidParameterMode { pc, .. } => pc,
+            IntcodeError::NegativeAddress { pc, .. } => pc,
+            IntcodeError::WriteInImmediateMode { pc, .. } => pc,
+            IntcodeError::InputExhausted { pc } => pc,
+            IntcodeError::IoFailure { pc, .. } => pc,
+            IntcodeError::IllegalTransition { pc, .. } => pc,
+            IntcodeError::BudgetExhausted { pc, .. } => pc,
+            IntcodeError::InfiniteLoop { pc, .. } => pc,
+            IntcodeError::ArithmeticOverflow { pc, .. } => pc,
+            IntcodeError::AddressOutOfRange { pc, .. } => pc,
+            IntcodeError::UndeclaredWrite { pc, .. } => pc
+        }
+    }
+
+    /// Re-anchor the error at `at_pc`. Decoding helpers that don't know where they are in
+    /// memory report pc 0 and the machine fills in the real location.
+    pub fn at(mut self, at_pc: usize) -> Self {
+        match self {
+            IntcodeError::InvalidOpcode { ref mut pc, .. } => *pc = at_pc,
+            IntcodeError::InvalidParameterMode { ref mut pc, .. } => *pc = at_pc,
+            IntcodeError::NegativeAddress { ref mut pc, .. } => *pc = at_pc,
+            IntcodeError::WriteInImmediateMode { ref mut pc, .. } => *pc = at_pc,
+            IntcodeError::InputExhausted { ref mut pc } => *pc = at_pc,
+            IntcodeError::IoFailure { ref mut pc, .. } => *pc = at_pc,
+            IntcodeError::IllegalTransition { ref mut pc, .. } => *pc = at_pc,
+            IntcodeError::BudgetExhausted { ref mut pc, .. } => *pc = at_pc,
+            IntcodeError::InfiniteLoop { ref mut pc, .. } => *pc = at_pc,
+            IntcodeError::ArithmeticOverflow { ref mut pc, .. } => *pc = at_pc,
+            IntcodeError::AddressOutOfRange { ref mut pc, .. } => *pc = at_pc,
+            IntcodeError::UndeclaredWrite { ref mut pc, .. } => *pc = at_pc
+        }
+
+        self
+    }
+}
+
+impl fmt::Display for IntcodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntcodeError::InvalidOpcode { pc, instruction } =>
+                write!(f, "invalid opcode in instruction {} at pc {}", instruction, pc),
+            IntcodeError::InvalidParameterMode { pc, instruction, mode } =>
+                write!(f, "invalid parameter mode {} in instruction {} at pc {}", mode, instruction, pc),
+            IntcodeError::NegativeAddress { pc, address } =>
+                write!(f, "negative memory address {} at pc {}", address, pc),
+            IntcodeError::WriteInImmediateMode { pc, instruction } =>
+                write!(f, "write target in immediate mode in instruction {} at pc {}", instruction, pc),
+            IntcodeError::InputExhausted { pc } =>
+                write!(f, "input exhausted at pc {}", pc),
+            IntcodeError::IoFailure { pc, message } =>
+                write!(f, "i/o failure at pc {}: {}", pc, message),
+            IntcodeError::IllegalTransition { pc, from, to } =>
+                write!(f, "illegal transition from {:?} to {:?} at pc {}", from, to, pc),
+            IntcodeError::BudgetExhausted { pc, cycles } =>
+                write!(f, "instruction budget exhausted after {} cycles at pc {}", cycles, pc),
+            IntcodeError::InfiniteLoop { pc, cycles } =>
+                write!(f, "infinite loop detected after {} cycles at pc {}", cycles, pc),
+            IntcodeError::ArithmeticOverflow { pc, instruction } =>
+                write!(f, "arithmetic overflow in instruction {} at pc {}", instruction, pc),
+            IntcodeError::AddressOutOfRange { pc, address } =>
+                write!(f, "memory address {} out of range at pc {}", address, pc),
+            IntcodeError::UndeclaredWrite { pc, opcode, operand } =>
+                write!(f, "opcode {} wrote through undeclared operand {} at pc {}", opcode, operand, pc)
+        }
+    }
+}
+
+impl std::error::Error for IntcodeError {}
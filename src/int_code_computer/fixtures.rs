@@ -0,0 +1,10 @@
+// Programs shared by the test modules.
+
+/// Sums 3 + 2 + 1 into [20] by counting [21] down to zero.
+pub const COUNTDOWN : [i64; 22] = [1101,3,0,21,1,20,21,20,1001,21,-1,21,1005,21,4,99,0,0,0,0,0,0];
+
+/// Reads a value, squares it and outputs it.
+pub const SQUARE : [i64; 9] = [3,0,2,0,0,0,4,0,99];
+
+/// Day 7's second example: an amplifier meant to run in a feedback loop of five.
+pub const FEEDBACK : [i64; 29] = [3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
@@ -0,0 +1,136 @@
+use crate::int_code_computer::MachineState;
+use crate::int_code_computer::word::Word;
+
+/// Everything needed to undo one executed instruction.
+#[derive(PartialEq, Clone, Debug)]
+pub struct UndoRecord<W: Word = i64> {
+    pub program_counter: usize,
+    pub relative_base_offset: i64,
+    pub state: MachineState,
+    /// The cells the instruction overwrote and the values they held before.
+    pub writes: Vec<(usize, W)>,
+    /// Values consumed by an `INPUT` (or a custom instruction); undoing puts them back at
+    /// the front of the queue.
+    pub inputs: Vec<W>,
+    /// How many values were appended to the machine's output buffer.
+    pub outputs: usize
+}
+
+/// The undo log kept while a machine is recording. Record `n` undoes cycle `start + n`.
+#[derive(PartialEq, Clone, Debug)]
+pub struct History<W: Word = i64> {
+    records: Vec<UndoRecord<W>>,
+    start: u64
+}
+
+impl<W: Word> History<W> {
+    pub fn new(start: u64) -> Self {
+        Self { records: vec![], start }
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn records(&self) -> &[UndoRecord<W>] {
+        &self.records
+    }
+
+    /// The earliest cycle the machine can rewind to.
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// The cycle of the most recent recorded write to `address`.
+    pub fn last_write(&self, address: usize) -> Option<u64> {
+        self.records.iter()
+            .rposition(|record| record.writes.iter().any(|(target, _)| *target == address))
+            .map(|index| self.start + index as u64)
+    }
+
+    pub(crate) fn push(&mut self, record: UndoRecord<W>) {
+        self.records.push(record);
+    }
+
+    pub(crate) fn pop(&mut self) -> Option<UndoRecord<W>> {
+        self.records.pop()
+    }
+}
+
+#[cfg(test)]
+mod history_tests {
+    use crate::int_code_computer::history::*;
+    use crate::int_code_computer::IntCodeMachine;
+    use crate::int_code_computer::fixtures::COUNTDOWN;
+
+    fn recorded() -> IntCodeMachine {
+        let mut machine = IntCodeMachine::new(&COUNTDOWN, None);
+
+        machine.set_recording(true);
+        machine.run().unwrap();
+        machine
+    }
+
+    #[test]
+    fn step_back_undoes_everything(){
+        let mut machine = recorded();
+
+        assert_eq!(machine.history().unwrap().len(), 11);
+
+        while machine.step_back() {}
+
+        assert_eq!(machine.memory.image(), &COUNTDOWN);
+        assert_eq!(machine.program_counter(), 0);
+        assert_eq!(machine.cycles(), 0);
+        assert_eq!(machine.state(), &MachineState::Ready);
+        assert_eq!(machine.run().unwrap(), MachineState::Halted);
+        assert_eq!(machine.memory.read(20), 6);
+    }
+
+    #[test]
+    fn run_back_to_previous_write(){
+        let mut machine = recorded();
+
+        assert_eq!(machine.history().unwrap().last_write(20), Some(7));
+        assert_eq!(machine.run_back_to_write(20), Some(7));
+        assert_eq!(machine.program_counter(), 4);
+        assert_eq!(machine.memory.read(20), 5);
+        assert_eq!(machine.run_back_to_write(20), Some(4));
+        assert_eq!(machine.memory.read(20), 3);
+        assert_eq!(machine.run_back_to_write(99), None);
+        assert_eq!(machine.cycles(), 4);
+    }
+
+    #[test]
+    fn rewind_to_cycle(){
+        let mut machine = recorded();
+
+        assert!(machine.rewind_to(5));
+        assert_eq!(machine.cycles(), 5);
+        assert_eq!(machine.program_counter(), 8);
+        assert!(!machine.rewind_to(6));
+
+        machine.step().unwrap();
+        assert_eq!(machine.memory.read(21), 1);
+    }
+
+    #[test]
+    fn undoes_io(){
+        let program = vec![3,9,1002,9,2,9,4,9,99,0];
+        let mut machine = IntCodeMachine::new(&program, Some(&vec![21]));
+
+        machine.set_recording(true);
+        machine.run().unwrap();
+        assert_eq!(machine.output, vec![42]);
+
+        assert!(machine.rewind_to(0));
+        assert!(machine.output.is_empty());
+        assert_eq!(machine.memory.read(9), 0);
+        assert_eq!(machine.run().unwrap(), MachineState::Halted);
+        assert_eq!(machine.output, vec![42]);
+    }
+}
@@ -0,0 +1,426 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use crate::int_code_computer::assembler::SymbolTable;
+use crate::int_code_computer::word::Word;
+
+const IMAGE_MAGIC: &[u8; 4] = b"ICIM";
+const IMAGE_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 10;
+const HAS_ENTRY_POINT: u8 = 1;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ImageError {
+    Io(String),
+    NotAnImage,
+    UnsupportedVersion(u8),
+    Checksum { expected: u32, actual: u32 },
+    /// The body ended in the middle of a field.
+    Truncated,
+    Invalid(String)
+}
+
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImageError::Io(message) => write!(f, "i/o failure: {}", message),
+            ImageError::NotAnImage => write!(f, "not an intcode image"),
+            ImageError::UnsupportedVersion(version) => write!(f, "unsupported image version {}", version),
+            ImageError::Checksum { expected, actual } =>
+                write!(f, "checksum mismatch: header says {:08x}, body is {:08x}", expected, actual),
+            ImageError::Truncated => write!(f, "image is truncated"),
+            ImageError::Invalid(message) => write!(f, "invalid image: {}", message)
+        }
+    }
+}
+
+impl std::error::Error for ImageError {}
+
+impl From<io::Error> for ImageError {
+    fn from(err: io::Error) -> Self {
+        ImageError::Io(err.to_string())
+    }
+}
+
+/// A program plus where to start it, in a compact binary container.
+///
+/// ```text
+/// "ICIM" version:u8 flags:u8 checksum:u32le
+/// [entry point]  relative base  symbol count  (name length, name, address)*
+/// word count  word*
+/// ```
+///
+/// Everything after the header is varints: unsigned LEB128, with signed values
+/// zigzag-encoded first so small negative numbers stay short. Words of any width are
+/// encoded the same way, so an image saved from i64 words loads as BigInt words and as
+/// i32 words as long as every word fits. The entry point is only present when bit 0 of
+/// the flags is set. The checksum is 32-bit FNV-1a over the body.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramImage<W: Word = i64> {
+    pub program: Vec<W>,
+    /// Where execution starts; address 0 if None.
+    pub entry_point: Option<usize>,
+    pub relative_base: i64,
+    pub symbols: SymbolTable
+}
+
+impl<W: Word> ProgramImage<W> {
+    pub fn new(program: &[W]) -> Self {
+        Self { program: program.to_vec(), entry_point: None, relative_base: 0, symbols: SymbolTable::new() }
+    }
+
+    /// The program as comma-separated text, which `loader::load_str` reads back.
+    /// Everything but the words is dropped.
+    pub fn to_text(&self) -> String {
+        self.program.iter().map(|word| word.to_string()).collect::<Vec<String>>().join(",")
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut body = vec![];
+
+        if let Some(entry_point) = self.entry_point {
+            write_unsigned(&mut body, entry_point as u64);
+        }
+
+        write_signed(&mut body, &self.relative_base.to_signed_bytes());
+        write_unsigned(&mut body, self.symbols.len() as u64);
+
+        for (name, address) in self.symbols.iter() {
+            write_unsigned(&mut body, name.len() as u64);
+            body.extend_from_slice(name.as_bytes());
+            write_unsigned(&mut body, *address as u64);
+        }
+
+        write_unsigned(&mut body, self.program.len() as u64);
+
+        for word in self.program.iter() {
+            write_signed(&mut body, &word.to_signed_bytes());
+        }
+
+        let flags = match self.entry_point {
+            Some(_) => HAS_ENTRY_POINT,
+            None => 0
+        };
+        let mut bytes = IMAGE_MAGIC.to_vec();
+
+        bytes.push(IMAGE_VERSION);
+        bytes.push(flags);
+        bytes.extend_from_slice(&checksum(&body).to_le_bytes());
+        bytes.extend(body);
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ImageError> {
+        if bytes.len() < HEADER_SIZE || &bytes[..4] != IMAGE_MAGIC {
+            return Err(ImageError::NotAnImage);
+        }
+
+        if bytes[4] != IMAGE_VERSION {
+            return Err(ImageError::UnsupportedVersion(bytes[4]));
+        }
+
+        let flags = bytes[5];
+        let expected = u32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+        let body = &bytes[HEADER_SIZE..];
+        let actual = checksum(body);
+
+        if expected != actual {
+            return Err(ImageError::Checksum { expected, actual });
+        }
+
+        if flags & !HAS_ENTRY_POINT != 0 {
+            return Err(ImageError::Invalid(format!("unknown flags {:#04x}", flags)));
+        }
+
+        let mut reader = Reader { bytes: body, position: 0 };
+        let entry_point = match flags & HAS_ENTRY_POINT {
+            0 => None,
+            _ => Some(reader.size("entry point")?)
+        };
+        let relative_base = reader.word::<i64>("relative base")?;
+        let mut symbols = SymbolTable::new();
+
+        for _ in 0..reader.size("symbol count")? {
+            let length = reader.size("symbol name length")?;
+            let name = String::from_utf8(reader.take(length)?.to_vec())
+                .map_err(|_| ImageError::Invalid(String::from("symbol name is not utf-8")))?;
+            let address = reader.size("symbol address")?;
+
+            symbols.insert(name, address);
+        }
+
+        let count = reader.size("word count")?;
+        // Every word takes at least a byte, so a corrupt count can't make us allocate
+        // more than the image could hold.
+        let mut program = Vec::with_capacity(count.min(body.len()));
+
+        for index in 0..count {
+            program.push(reader.word::<W>(&format!("word {}", index))?);
+        }
+
+        if reader.position != body.len() {
+            return Err(ImageError::Invalid(format!("{} bytes after the last word", body.len() - reader.position)));
+        }
+
+        Ok(Self { program, entry_point, relative_base, symbols })
+    }
+
+    pub fn save<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+        writer.write_all(&self.to_bytes())?;
+        writer.flush()
+    }
+
+    pub fn load<R: Read>(mut reader: R) -> Result<Self, ImageError> {
+        let mut bytes = vec![];
+
+        reader.read_to_end(&mut bytes)?;
+        Self::from_bytes(&bytes)
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.save(&mut BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, ImageError> {
+        Self::load(BufReader::new(File::open(path)?))
+    }
+}
+
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c_9dc5, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x0100_0193))
+}
+
+// Unsigned little-endian bytes as LEB128.
+fn write_varint(out: &mut Vec<u8>, bytes: &[u8]) {
+    let mut groups = vec![];
+    let (mut pending, mut bits) = (0u32, 0);
+
+    for byte in bytes {
+        pending |= (*byte as u32) << bits;
+        bits += 8;
+
+        while bits >= 7 {
+            groups.push((pending & 0x7f) as u8);
+            pending >>= 7;
+            bits -= 7;
+        }
+    }
+
+    groups.push(pending as u8);
+
+    while groups.len() > 1 && groups[groups.len() - 1] == 0 {
+        groups.pop();
+    }
+
+    let last = groups.len() - 1;
+
+    out.extend(groups.iter().enumerate().map(|(index, group)| match index == last {
+        true => *group,
+        false => group | 0x80
+    }));
+}
+
+fn write_unsigned(out: &mut Vec<u8>, value: u64) {
+    write_varint(out, &value.to_le_bytes());
+}
+
+// Zigzag: 0, -1, 1, -2, ... become 0, 1, 2, 3, ... whatever the width.
+fn write_signed(out: &mut Vec<u8>, signed: &[u8]) {
+    let negative = signed.last().is_some_and(|last| last & 0x80 != 0);
+    let mut magnitude = signed.iter().map(|byte| if negative { !byte } else { *byte }).collect::<Vec<u8>>();
+    let mut carry = negative as u8;
+
+    // The magnitude's top bit is clear, so the shift can't carry out of it.
+    for byte in magnitude.iter_mut() {
+        let next = *byte >> 7;
+
+        *byte = (*byte << 1) | carry;
+        carry = next;
+    }
+
+    write_varint(out, &magnitude);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], ImageError> {
+        let end = self.position.checked_add(length).filter(|end| *end <= self.bytes.len()).ok_or(ImageError::Truncated)?;
+        let taken = &self.bytes[self.position..end];
+
+        self.position = end;
+
+        Ok(taken)
+    }
+
+    // LEB128 back to unsigned little-endian bytes.
+    fn varint(&mut self) -> Result<Vec<u8>, ImageError> {
+        let mut bytes = vec![];
+        let (mut pending, mut bits) = (0u32, 0);
+
+        loop {
+            let group = self.take(1)?[0];
+
+            pending |= ((group & 0x7f) as u32) << bits;
+            bits += 7;
+
+            if bits >= 8 {
+                bytes.push(pending as u8);
+                pending >>= 8;
+                bits -= 8;
+            }
+
+            if group & 0x80 == 0 {
+                bytes.push(pending as u8);
+                return Ok(bytes);
+            }
+        }
+    }
+
+    fn size(&mut self, field: &str) -> Result<usize, ImageError> {
+        let bytes = self.varint()?;
+        let too_large = || ImageError::Invalid(format!("{} is too large", field));
+
+        if bytes.iter().skip(8).any(|byte| *byte != 0) {
+            return Err(too_large());
+        }
+
+        let mut value = [0; 8];
+        let kept = bytes.len().min(8);
+
+        value[..kept].copy_from_slice(&bytes[..kept]);
+        usize::try_from(u64::from_le_bytes(value)).map_err(|_| too_large())
+    }
+
+    fn word<W: Word>(&mut self, field: &str) -> Result<W, ImageError> {
+        let mut magnitude = self.varint()?;
+        let negative = magnitude[0] & 1 != 0;
+
+        // Undo the shift, leaving a clear top bit so the bytes read as non-negative.
+        for index in 0..magnitude.len() {
+            let next = magnitude.get(index + 1).map_or(0, |byte| byte & 1);
+
+            magnitude[index] = (magnitude[index] >> 1) | (next << 7);
+        }
+
+        magnitude.push(0);
+
+        let signed = magnitude.iter().map(|byte| if negative { !byte } else { *byte }).collect::<Vec<u8>>();
+
+        W::from_signed_bytes(&signed).ok_or_else(|| ImageError::Invalid(format!("{} doesn't fit in a word", field)))
+    }
+}
+
+#[cfg(test)]
+mod image_tests {
+    use crate::int_code_computer::image::*;
+    use crate::int_code_computer::assembler::assemble_with_symbols;
+    use crate::int_code_computer::IntCodeMachine;
+    use crate::int_code_computer::loader::load_str;
+    use num_bigint::BigInt;
+
+    fn signed(value: i64) -> Vec<u8> {
+        let mut out = vec![];
+
+        write_signed(&mut out, &value.to_signed_bytes());
+        out
+    }
+
+    #[test]
+    fn zigzag_varints(){
+        assert_eq!(signed(0), vec![0]);
+        assert_eq!(signed(-1), vec![1]);
+        assert_eq!(signed(1), vec![2]);
+        assert_eq!(signed(-64), vec![127]);
+        assert_eq!(signed(300), vec![0xd8, 0x04]);
+        assert_eq!(signed(i64::MIN).len(), 10);
+
+        for value in [0, 1, -1, 63, -64, 64, 300, -300, i64::MAX, i64::MIN].iter() {
+            let bytes = signed(*value);
+
+            assert_eq!(Reader { bytes : &bytes, position : 0 }.word::<i64>("word"), Ok(*value));
+        }
+    }
+
+    #[test]
+    fn round_trips_with_header_fields(){
+        let (program, symbols) = assemble_with_symbols("
+                    hlt
+            start:  out [rb+2]
+                    hlt
+            value:  data 7, -300
+        ").unwrap();
+        let image = ProgramImage { program, entry_point : Some(symbols["start"]), relative_base : 3, symbols };
+        let bytes = image.to_bytes();
+
+        assert_eq!(&bytes[..6], b"ICIM\x01\x01");
+        assert_eq!(ProgramImage::from_bytes(&bytes), Ok(image.clone()));
+        assert_eq!(image.to_text(), "99,204,2,99,7,-300");
+        assert_eq!(load_str::<i64>(&image.to_text()), Ok(image.program.clone()));
+
+        let mut machine = IntCodeMachine::from_image(&image);
+
+        machine.run().unwrap();
+        assert_eq!(machine.output, vec![-300]);
+    }
+
+    #[test]
+    fn words_load_at_any_width_that_fits(){
+        let big : BigInt = BigInt::from(-3) << 100;
+        let bytes = ProgramImage::new(&[BigInt::from(104), big.clone(), BigInt::from(99)]).to_bytes();
+
+        assert_eq!(ProgramImage::<BigInt>::from_bytes(&bytes).unwrap().program[1], big);
+        assert_eq!(ProgramImage::<i64>::from_bytes(&bytes), Err(ImageError::Invalid(String::from("word 1 doesn't fit in a word"))));
+
+        let bytes = ProgramImage::new(&[104i64, -70000, 99]).to_bytes();
+
+        assert_eq!(ProgramImage::<i32>::from_bytes(&bytes).unwrap().program, vec![104, -70000, 99]);
+    }
+
+    #[test]
+    fn rejects_damaged_images(){
+        let mut bytes = ProgramImage::new(&[104i64, 5, 99]).to_bytes();
+
+        assert_eq!(ProgramImage::<i64>::from_bytes(b"INTCODE"), Err(ImageError::NotAnImage));
+
+        bytes[4] = 2;
+        assert_eq!(ProgramImage::<i64>::from_bytes(&bytes), Err(ImageError::UnsupportedVersion(2)));
+
+        bytes[4] = 1;
+        bytes[HEADER_SIZE + 3] ^= 1;
+        assert!(matches!(ProgramImage::<i64>::from_bytes(&bytes), Err(ImageError::Checksum { .. })));
+
+        let mut body = vec![];
+
+        write_unsigned(&mut body, 0);
+        write_unsigned(&mut body, 0);
+        write_unsigned(&mut body, 3);
+
+        let mut truncated = b"ICIM\x01\x00".to_vec();
+
+        truncated.extend_from_slice(&checksum(&body).to_le_bytes());
+        truncated.extend(body);
+        assert_eq!(ProgramImage::<i64>::from_bytes(&truncated), Err(ImageError::Truncated));
+    }
+
+    #[test]
+    fn machines_load_day_9_from_a_file(){
+        let program : Vec<i64> = IntCodeMachine::read_file_into_program("day-9-part-1-input").unwrap();
+        let path = std::env::temp_dir().join(format!("day-9-{}.icim", std::process::id()));
+
+        ProgramImage::new(&program).save_to_file(&path).unwrap();
+
+        let mut machine = IntCodeMachine::<i64>::load_image(&path).unwrap();
+
+        std::fs::remove_file(&path).unwrap();
+        machine.push_input(1).unwrap();
+        machine.run().unwrap();
+        assert_eq!(machine.output, vec![2494485073]);
+    }
+}
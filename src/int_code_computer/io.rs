@@ -0,0 +1,290 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use crate::int_code_computer::word::Word;
+
+/// Something a machine can pull input values from.
+///
+/// `Ok(None)` means nothing is available right now; the machine parks in
+/// `MachineState::AwaitingInput` and asks again when it is next resumed.
+pub trait InputSource<W: Word = i64> {
+    fn next_input(&mut self) -> io::Result<Option<W>>;
+}
+
+/// Something a machine can push output values into.
+pub trait OutputSink<W: Word = i64> {
+    fn write_output(&mut self, value: W) -> io::Result<()>;
+}
+
+impl<W: Word> InputSource<W> for VecDeque<W> {
+    fn next_input(&mut self) -> io::Result<Option<W>> {
+        Ok(self.pop_front())
+    }
+}
+
+impl<W: Word> OutputSink<W> for VecDeque<W> {
+    fn write_output(&mut self, value: W) -> io::Result<()> {
+        self.push_back(value);
+        Ok(())
+    }
+}
+
+impl<W: Word> OutputSink<W> for Vec<W> {
+    fn write_output(&mut self, value: W) -> io::Result<()> {
+        self.push(value);
+        Ok(())
+    }
+}
+
+// Shared handles let one machine's sink be another machine's source.
+impl<W: Word, T: InputSource<W>> InputSource<W> for Rc<RefCell<T>> {
+    fn next_input(&mut self) -> io::Result<Option<W>> {
+        self.borrow_mut().next_input()
+    }
+}
+
+impl<W: Word, T: OutputSink<W>> OutputSink<W> for Rc<RefCell<T>> {
+    fn write_output(&mut self, value: W) -> io::Result<()> {
+        self.borrow_mut().write_output(value)
+    }
+}
+
+impl<W: Word> InputSource<W> for Receiver<W> {
+    fn next_input(&mut self) -> io::Result<Option<W>> {
+        match self.try_recv() {
+            Ok(value) => Ok(Some(value)),
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(io::Error::new(io::ErrorKind::BrokenPipe, "input channel disconnected"))
+        }
+    }
+}
+
+impl<W: Word> OutputSink<W> for Sender<W> {
+    fn write_output(&mut self, value: W) -> io::Result<()> {
+        self.send(value).map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "output channel disconnected"))
+    }
+}
+
+/// Pulls input from a closure.
+pub struct FnSource<F>(pub F);
+
+impl<W: Word, F: FnMut() -> Option<W>> InputSource<W> for FnSource<F> {
+    fn next_input(&mut self) -> io::Result<Option<W>> {
+        Ok((self.0)())
+    }
+}
+
+/// Hands every output to a closure.
+pub struct FnSink<F>(pub F);
+
+impl<W: Word, F: FnMut(W)> OutputSink<W> for FnSink<F> {
+    fn write_output(&mut self, value: W) -> io::Result<()> {
+        (self.0)(value);
+        Ok(())
+    }
+}
+
+/// Prompts on stdout and reads one value per line from stdin. Once stdin is closed
+/// there is no more input.
+pub struct StdinSource;
+
+impl<W: Word> InputSource<W> for StdinSource {
+    fn next_input(&mut self) -> io::Result<Option<W>> {
+        print!("Input: ");
+        io::stdout().flush()?;
+
+        read_value_line(&mut io::stdin().lock())
+    }
+}
+
+// One value on its own line, or None at end of input.
+fn read_value_line<W: Word, R: BufRead>(reader: &mut R) -> io::Result<Option<W>> {
+    let mut input = String::new();
+
+    if reader.read_line(&mut input)? == 0 {
+        return Ok(None);
+    }
+
+    parse_value(input.trim()).map(Some)
+}
+
+/// Prints one value per line to stdout.
+pub struct StdoutSink;
+
+impl<W: Word> OutputSink<W> for StdoutSink {
+    fn write_output(&mut self, value: W) -> io::Result<()> {
+        println!("{}", value);
+        Ok(())
+    }
+}
+
+/// Reads values separated by commas or whitespace from any reader, e.g. a file.
+pub struct ReaderSource<R: BufRead, W: Word = i64> {
+    reader: R,
+    pending: VecDeque<W>
+}
+
+impl<R: BufRead, W: Word> ReaderSource<R, W> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, pending: VecDeque::new() }
+    }
+}
+
+impl<W: Word> ReaderSource<BufReader<File>, W> {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(ReaderSource::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead, W: Word> InputSource<W> for ReaderSource<R, W> {
+    fn next_input(&mut self) -> io::Result<Option<W>> {
+        while self.pending.is_empty() {
+            let mut line = String::new();
+
+            if self.reader.read_line(&mut line)? == 0 {
+                return Ok(None);
+            }
+
+            for token in line.split(|c: char| c == ',' || c.is_whitespace()).filter(|token| !token.is_empty()) {
+                self.pending.push_back(parse_value(token)?);
+            }
+        }
+
+        Ok(self.pending.pop_front())
+    }
+}
+
+/// Writes one value per line to any writer, e.g. a file.
+pub struct WriterSink<T: Write> {
+    writer: T
+}
+
+impl<T: Write> WriterSink<T> {
+    pub fn new(writer: T) -> Self {
+        Self { writer }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.writer
+    }
+}
+
+impl WriterSink<BufWriter<File>> {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(WriterSink::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<T: Write, W: Word> OutputSink<W> for WriterSink<T> {
+    fn write_output(&mut self, value: W) -> io::Result<()> {
+        writeln!(self.writer, "{}", value)?;
+        self.writer.flush()
+    }
+}
+
+fn parse_value<W: Word>(token: &str) -> io::Result<W> {
+    token.parse::<W>().map_err(|err| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("could not parse {:?}: {}", token, err))
+    })
+}
+
+#[cfg(test)]
+mod io_tests {
+    use crate::int_code_computer::io::*;
+    use crate::int_code_computer::{IntCodeMachine, MachineState};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn reader_source_splits_commas_and_lines(){
+        let mut source = ReaderSource::new("1, 2\n\n-3\n4,".as_bytes());
+        let mut values : Vec<i64> = vec![];
+
+        while let Some(value) = source.next_input().unwrap() {
+            values.push(value);
+        }
+
+        assert_eq!(values, vec![1, 2, -3, 4]);
+    }
+
+    #[test]
+    fn reader_source_rejects_garbage(){
+        let mut source : ReaderSource<_, i64> = ReaderSource::new("1O1".as_bytes());
+
+        assert_eq!(source.next_input().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn value_lines_end_at_eof(){
+        let mut reader = "7\n -2 \n".as_bytes();
+
+        assert_eq!(read_value_line::<i64, _>(&mut reader).unwrap(), Some(7));
+        assert_eq!(read_value_line::<i64, _>(&mut reader).unwrap(), Some(-2));
+        assert_eq!(read_value_line::<i64, _>(&mut reader).unwrap(), None);
+        assert_eq!(read_value_line::<i64, _>(&mut "x\n".as_bytes()).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn writer_sink_writes_lines(){
+        let mut sink = WriterSink::new(vec![]);
+
+        sink.write_output(1).unwrap();
+        sink.write_output(-2).unwrap();
+
+        assert_eq!(sink.into_inner(), b"1\n-2\n");
+    }
+
+    #[test]
+    fn closure_source_and_sink(){
+        let program = vec![3,11,3,12,1,11,12,13,4,13,99];
+        let mut inputs = vec![40, 2].into_iter();
+        let outputs = Rc::new(RefCell::new(vec![]));
+        let collected = outputs.clone();
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        machine.set_input_source(FnSource(move || inputs.next()));
+        machine.set_output_sink(FnSink(move |value| collected.borrow_mut().push(value)));
+
+        assert_eq!(machine.run().unwrap(), MachineState::Halted);
+        assert!(machine.output.is_empty());
+        assert_eq!(*outputs.borrow(), vec![42]);
+    }
+
+    #[test]
+    fn shared_queue_between_machines(){
+        let double = vec![3,9,102,2,9,9,4,9,99];
+        let link = Rc::new(RefCell::new(VecDeque::new()));
+        let mut first = IntCodeMachine::new(&double, Some(&vec![5]));
+        let mut second = IntCodeMachine::new(&double, None);
+
+        first.set_output_sink(link.clone());
+        second.set_input_source(link);
+
+        first.run().unwrap();
+        second.run().unwrap();
+
+        assert_eq!(second.output, vec![20]);
+    }
+
+    #[test]
+    fn channel_chain(){
+        let double = vec![3,9,102,2,9,9,4,9,99];
+        let (sender, receiver) = channel();
+        let (result_sender, result_receiver) = channel();
+        let mut first = IntCodeMachine::new(&double, Some(&vec![3]));
+        let mut second = IntCodeMachine::new(&double, None);
+
+        first.set_output_sink(sender);
+        second.set_input_source(receiver);
+        second.set_output_sink(result_sender);
+
+        assert_eq!(second.run().unwrap(), MachineState::AwaitingInput);
+        first.run().unwrap();
+        assert_eq!(second.run().unwrap(), MachineState::Halted);
+
+        assert_eq!(result_receiver.try_recv().unwrap(), 12);
+    }
+}
@@ -0,0 +1,158 @@
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use crate::int_code_computer::word::Word;
+
+/// Why a program couldn't be loaded. Lines and columns count from 1, columns in
+/// characters.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LoadError {
+    Io { source: String, message: String },
+    Syntax { token: String, line: usize, column: usize, message: String }
+}
+
+impl LoadError {
+    fn syntax(token: &str, (line, column): (usize, usize), message: String) -> Self {
+        LoadError::Syntax { token: token.to_string(), line, column, message }
+    }
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { source, message } => write!(f, "could not read {}: {}", source, message),
+            LoadError::Syntax { token, line, column, message } => write!(f, "{}:{}: {}: {:?}", line, column, message, token)
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Parse program text: words separated by commas, with any whitespace or newlines
+/// around them and `#` comments running to the end of the line.
+///
+/// ```text
+/// # doubles its input
+/// 3,9,
+/// 1002,9,2,9,   # [9] *= 2
+/// 4,9,99,0
+/// ```
+///
+/// Every word has to parse; a missing word between commas, two words without a comma
+/// between them and a trailing comma are all errors.
+pub fn load_str<W: Word>(text: &str) -> Result<Vec<W>, LoadError> {
+    let mut program = vec![];
+    // Where the last comma was, while no word has followed it yet.
+    let mut comma = None;
+
+    for (index, line) in text.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or("");
+        let mut chars = code.char_indices().peekable();
+        let mut column = 0;
+
+        while let Some((start, c)) = chars.next() {
+            column += 1;
+
+            let position = (index + 1, column);
+
+            match c {
+                _ if c.is_whitespace() => {},
+                ',' if comma.is_some() || program.is_empty() => {
+                    return Err(LoadError::syntax(",", position, String::from("expected a word before ','")));
+                },
+                ',' => comma = Some(position),
+                _ => {
+                    let mut end = start + c.len_utf8();
+
+                    while let Some((next, c)) = chars.peek().cloned() {
+                        if c.is_whitespace() || c == ',' {
+                            break;
+                        }
+
+                        end = next + c.len_utf8();
+                        column += 1;
+                        chars.next();
+                    }
+
+                    let token = &code[start..end];
+
+                    if comma.is_none() && !program.is_empty() {
+                        return Err(LoadError::syntax(token, position, String::from("expected ',' before word")));
+                    }
+
+                    let word = token.parse::<W>()
+                        .map_err(|err| LoadError::syntax(token, position, format!("invalid word ({})", err)))?;
+
+                    program.push(word);
+                    comma = None;
+                }
+            }
+        }
+    }
+
+    match comma {
+        Some(position) => Err(LoadError::syntax(",", position, String::from("trailing ','"))),
+        None => Ok(program)
+    }
+}
+
+pub fn load_reader<W: Word, R: Read>(mut reader: R) -> Result<Vec<W>, LoadError> {
+    let mut text = String::new();
+
+    reader.read_to_string(&mut text)
+        .map_err(|err| LoadError::Io { source: String::from("reader"), message: err.to_string() })?;
+
+    load_str(&text)
+}
+
+pub fn load_path<W: Word, P: AsRef<Path>>(path: P) -> Result<Vec<W>, LoadError> {
+    let path = path.as_ref();
+    let text = fs::read_to_string(path)
+        .map_err(|err| LoadError::Io { source: path.display().to_string(), message: err.to_string() })?;
+
+    load_str(&text)
+}
+
+#[cfg(test)]
+mod loader_tests {
+    use crate::int_code_computer::loader::*;
+    use num_bigint::BigInt;
+
+    fn syntax(token: &str, line: usize, column: usize, message: &str) -> LoadError {
+        LoadError::Syntax { token: token.to_string(), line, column, message: message.to_string() }
+    }
+
+    #[test]
+    fn tolerates_layout_and_comments(){
+        let program : Vec<i64> = load_str("# doubles its input\n3,9,\n  1002, 9,2,9,\t# [9] *= 2\r\n4,9,99,0\n").unwrap();
+
+        assert_eq!(program, vec![3,9,1002,9,2,9,4,9,99,0]);
+        assert_eq!(load_str::<i64>(" \n# nothing\n"), Ok(vec![]));
+        assert_eq!(load_str::<BigInt>("1,-99999999999999999999"), Ok(vec![BigInt::from(1), "-99999999999999999999".parse().unwrap()]));
+    }
+
+    #[test]
+    fn reports_bad_tokens(){
+        assert_eq!(load_str::<i64>("1,2,\n3,1O1,4"), Err(syntax("1O1", 2, 3, "invalid word (invalid digit found in string)")));
+        assert_eq!(load_str::<i64>("1,,2"), Err(syntax(",", 1, 3, "expected a word before ','")));
+        assert_eq!(load_str::<i64>("1,2\n3"), Err(syntax("3", 2, 1, "expected ',' before word")));
+        assert_eq!(load_str::<i64>("1,2,\n"), Err(syntax(",", 1, 4, "trailing ','")));
+        assert_eq!(load_str::<i32>("1,99999999999"), Err(syntax("99999999999", 1, 3, "invalid word (number too large to fit in target type)")));
+        assert_eq!(
+            load_str::<i64>("1,x").unwrap_err().to_string(),
+            "1:3: invalid word (invalid digit found in string): \"x\""
+        );
+    }
+
+    #[test]
+    fn loads_from_readers_and_paths(){
+        assert_eq!(load_reader::<i64, _>("104,7,99".as_bytes()), Ok(vec![104,7,99]));
+        assert!(load_path::<i64, _>("resources/day-9-part-1-input").unwrap().len() > 900);
+
+        match load_path::<i64, _>("resources/no-such-program") {
+            Err(LoadError::Io { source, .. }) => assert_eq!(source, "resources/no-such-program"),
+            other => panic!("{:?}", other)
+        }
+    }
+}
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+use crate::int_code_computer::IntcodeError;
+use crate::int_code_computer::engine::{DecodeCache, Decoded};
+use crate::int_code_computer::word::Word;
+
+pub const PAGE_SIZE: usize = 1024;
+
+// Page number -> page contents. Pages only exist once something has been written to them.
+type MemoryMap<W> = HashMap<usize, Vec<W>>;
+
+/// Sparse machine memory.
+///
+/// The original program image lives in a flat vector so the common case (executing and
+/// patching the program itself) is a plain index. Everything past the image is paged in
+/// on first write; reads of untouched cells return 0.
+#[derive(Clone, Debug)]
+pub struct Memory<W: Word = i64> {
+    image: Vec<W>,
+    pages: MemoryMap<W>,
+    fingerprint: u64,
+    // Only covers the image; writes there invalidate the instructions they land in.
+    decode_cache: Option<DecodeCache<W>>
+}
+
+// The decode cache is derived from the contents, so it doesn't take part in equality.
+impl<W: Word> PartialEq for Memory<W> {
+    fn eq(&self, other: &Self) -> bool {
+        self.image == other.image && self.pages == other.pages
+    }
+}
+
+// splitmix64 over the address and value. Zero cells hash to 0 so an untouched cell and
+// a cell explicitly set to 0 look the same, matching what `read` returns.
+fn cell_hash<W: Word>(address: usize, value: &W) -> u64 {
+    if value.is_zero() {
+        return 0;
+    }
+
+    let mut hash = (address as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^ value.hash_bits();
+
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^ (hash >> 31)
+}
+
+impl<W: Word> Memory<W> {
+    pub fn new(program: &[W]) -> Self {
+        let fingerprint = program.iter().enumerate()
+            .fold(0, |fingerprint, (address, value)| fingerprint ^ cell_hash(address, value));
+
+        Self {
+            image: program.to_vec(),
+            pages: HashMap::new(),
+            fingerprint,
+            decode_cache: None
+        }
+    }
+
+    /// A hash of the full memory contents, maintained incrementally on every write.
+    /// Equal memories always have equal fingerprints.
+    pub fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// The original program image, including any writes made to it.
+    pub fn image(&self) -> &[W] {
+        &self.image
+    }
+
+    pub fn read(&self, address: usize) -> W {
+        if address < self.image.len() {
+            return self.image[address].clone();
+        }
+
+        let (page, offset) = Self::page_of(address - self.image.len());
+
+        match self.pages.get(&page) {
+            Some(cells) => cells[offset].clone(),
+            None => W::from_i64(0)
+        }
+    }
+
+    pub fn write(&mut self, address: usize, value: W) {
+        self.fingerprint ^= cell_hash(address, &self.read(address)) ^ cell_hash(address, &value);
+
+        if address < self.image.len() {
+            self.image[address] = value;
+
+            if let Some(cache) = self.decode_cache.as_mut() {
+                cache.invalidate(address);
+            }
+
+            return;
+        }
+
+        let (page, offset) = Self::page_of(address - self.image.len());
+
+        self.pages.entry(page).or_insert_with(|| vec![W::from_i64(0); PAGE_SIZE])[offset] = value;
+    }
+
+    pub(crate) fn enable_decode_cache(&mut self) {
+        if self.decode_cache.is_none() {
+            self.decode_cache = Some(DecodeCache::new(self.image.len()));
+        }
+    }
+
+    pub(crate) fn disable_decode_cache(&mut self) {
+        self.decode_cache = None;
+    }
+
+    // Code outside the image is decoded every time; it's rare enough not to be worth caching.
+    pub(crate) fn decode_cached(&mut self, address: usize) -> Result<Decoded<W>, IntcodeError> {
+        if let Some(decoded) = self.decode_cache.as_ref().and_then(|cache| cache.get(address)) {
+            return Ok(decoded);
+        }
+
+        let decoded = Decoded::decode(self, address)?;
+
+        if let Some(cache) = self.decode_cache.as_mut() {
+            cache.insert(address, decoded.clone());
+        }
+
+        Ok(decoded)
+    }
+
+    pub fn allocated_pages(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Allocated pages past the image, in page order.
+    pub fn pages(&self) -> Vec<(usize, &[W])> {
+        let mut pages : Vec<(usize, &[W])> = self.pages.iter()
+            .map(|(page, cells)| (*page, cells.as_slice()))
+            .collect();
+
+        pages.sort_by_key(|(page, _)| *page);
+        pages
+    }
+
+    pub(crate) fn load_page(&mut self, page: usize, cells: Vec<W>) {
+        let base = self.image.len() + page * PAGE_SIZE;
+        let page_hash = |cells: &[W]| cells.iter().enumerate()
+            .fold(0, |fingerprint, (offset, value)| fingerprint ^ cell_hash(base + offset, value));
+
+        self.fingerprint ^= page_hash(&cells);
+
+        if let Some(previous) = self.pages.insert(page, cells) {
+            self.fingerprint ^= page_hash(&previous);
+        }
+    }
+
+    fn page_of(address: usize) -> (usize, usize) {
+        (address / PAGE_SIZE, address % PAGE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod memory_tests {
+    use crate::int_code_computer::memory::*;
+
+    #[test]
+    fn reads_program_image(){
+        let memory = Memory::new(&[1, 2, 3]);
+
+        assert_eq!(memory.read(0), 1);
+        assert_eq!(memory.read(2), 3);
+        assert_eq!(memory.allocated_pages(), 0);
+    }
+
+    #[test]
+    fn unwritten_cells_read_zero(){
+        let memory = Memory::new(&[1, 2, 3]);
+
+        assert_eq!(memory.read(3), 0);
+        assert_eq!(memory.read(1_000_000_000), 0);
+        assert_eq!(memory.allocated_pages(), 0);
+    }
+
+    #[test]
+    fn writes_allocate_pages_on_demand(){
+        let mut memory = Memory::new(&[1, 2, 3]);
+
+        memory.write(1, 20);
+        assert_eq!(memory.allocated_pages(), 0);
+
+        memory.write(3, 7);
+        memory.write(3 + PAGE_SIZE - 1, 8);
+        assert_eq!(memory.allocated_pages(), 1);
+
+        memory.write(1_000_000_000, 9);
+        assert_eq!(memory.allocated_pages(), 2);
+
+        assert_eq!(memory.image(), &[1, 20, 3]);
+        assert_eq!(memory.read(3), 7);
+        assert_eq!(memory.read(3 + PAGE_SIZE - 1), 8);
+        assert_eq!(memory.read(1_000_000_000), 9);
+    }
+
+    #[test]
+    fn fingerprint_tracks_contents(){
+        let mut memory = Memory::new(&[1, 2, 3]);
+        let original = memory.fingerprint();
+
+        memory.write(1, 5);
+        memory.write(5000, 6);
+        assert_ne!(memory.fingerprint(), original);
+
+        memory.write(1, 2);
+        memory.write(5000, 0);
+        assert_eq!(memory.fingerprint(), original);
+        assert_eq!(Memory::new(&[1, 2, 3, 0, 0]).fingerprint(), original);
+    }
+}
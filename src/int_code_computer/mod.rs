@@ -1,25 +1,75 @@
-use crate::read_input_file;
-use std::io;
-use std::io::Write;
-use std::collections::HashMap;
-use std::hash::Hash;
-
-type MemoryMap = HashMap<(usize, usize), Vec<i64>>;
+pub mod memory;
+pub mod error;
+pub mod state;
+pub mod io;
+pub mod disassembler;
+pub mod assembler;
+pub mod snapshot;
+pub mod profiler;
+pub mod debugger;
+pub mod history;
+pub mod engine;
+pub mod code_monitor;
+pub mod arithmetic;
+pub mod word;
+pub mod registry;
+pub mod async_machine;
+pub mod circuit;
+pub mod network;
+pub mod symbolic;
+pub mod solver;
+pub mod loader;
+pub mod image;
+#[cfg(test)]
+mod fixtures;
+
+use crate::resource_path;
+use std::collections::{HashSet, VecDeque};
+use memory::Memory;
+pub use error::IntcodeError;
+pub use state::{MachineState, StopReason};
+pub use io::{InputSource, OutputSink};
+use disassembler::Listing;
+use snapshot::Snapshot;
+use profiler::Profile;
+use debugger::{BreakReason, Breakpoints, Step};
+use history::{History, UndoRecord};
+use engine::Engine;
+use code_monitor::CodeMonitor;
+use arithmetic::Arithmetic;
+pub use word::Word;
+use loader::LoadError;
+use image::{ImageError, ProgramImage};
+use registry::{CustomOpcode, HostEffects, Host, Registry};
 
 #[derive(PartialEq, Copy, Clone, Debug)]
-pub struct OpcodeArg {
+pub struct OpcodeArg<W : Word = i64> {
     parameter_mode : i64,
-    value: i64,
+    value: W,
     address : i64
 }
 
-impl OpcodeArg {
-    fn new(parameter_mode : i64, value : i64, address : i64) -> Self{
+impl<W : Word> OpcodeArg<W> {
+    fn new(parameter_mode : i64, value : W, address : i64) -> Self{
         Self {parameter_mode, value, address }
     }
+
+    pub fn mode(&self) -> i64 {
+        self.parameter_mode
+    }
+
+    /// The operand's resolved value: the immediate itself, or what was in memory at `address`.
+    pub fn value(&self) -> W {
+        self.value.clone()
+    }
+
+    /// The memory cell the operand refers to. For immediates this is just the raw word.
+    pub fn address(&self) -> i64 {
+        self.address
+    }
 }
 
-#[derive(PartialEq, Copy, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
 pub enum Opcode{
     Add,
     Mult,
@@ -30,7 +80,8 @@ pub enum Opcode{
     JumpIfFalse,
     LessThan,
     Equals,
-    RBO
+    RBO,
+    Custom(CustomOpcode)
 }
 
 impl std::fmt::Display for Opcode {
@@ -46,14 +97,45 @@ impl std::fmt::Display for Opcode {
             Opcode::Input => "INPUT",
             Opcode::Output => "OUTPUT",
             Opcode::RBO => "RBO",
+            Opcode::Custom(custom) => custom.mnemonic
         };
 
         write!(f, "{}", name)
     }
 }
 
+impl std::str::FromStr for Opcode {
+    type Err = String;
+
+    // Inverse of Display, ignoring case.
+    fn from_str(name : &str) -> Result<Self, Self::Err> {
+        Opcode::ALL.iter()
+            .find(|opcode| opcode.to_string().eq_ignore_ascii_case(name))
+            .cloned()
+            .ok_or_else(|| format!("unknown opcode {:?}", name))
+    }
+}
+
 impl Opcode {
-    pub fn new(input : i64) -> Result<(Self, i64), &'static str>{
+    pub const ALL : [Opcode; 10] = [
+        Opcode::Add,
+        Opcode::Mult,
+        Opcode::Input,
+        Opcode::Output,
+        Opcode::JumpIfTrue,
+        Opcode::JumpIfFalse,
+        Opcode::LessThan,
+        Opcode::Equals,
+        Opcode::RBO,
+        Opcode::ProgramEnd
+    ];
+
+    /// Split an instruction word into its opcode and parameter modes.
+    pub fn new<W : Word>(word : W) -> Result<(Self, i64), IntcodeError>{
+        let input = match word.to_i64() {
+            Some(input) => input,
+            None => return Err(IntcodeError::InvalidOpcode { pc : 0, instruction : word.saturating_i64() })
+        };
         let parameter_mode = input/100;
         let opcode_input = input - (parameter_mode * 100);
 
@@ -68,12 +150,52 @@ impl Opcode {
             8 => Opcode::Equals,
             9 => Opcode::RBO,
             99 => Opcode::ProgramEnd,
-            _ => return Err("Invalid Opcode Input")
+            _ => return Err(IntcodeError::InvalidOpcode { pc : 0, instruction : input })
         };
 
         Ok((opcode, parameter_mode))
     }
 
+    /// The numeric opcode, without any parameter modes.
+    pub fn code(&self) -> i64 {
+        match *self {
+            Opcode::Add => 1,
+            Opcode::Mult => 2,
+            Opcode::Input => 3,
+            Opcode::Output => 4,
+            Opcode::JumpIfTrue => 5,
+            Opcode::JumpIfFalse => 6,
+            Opcode::LessThan => 7,
+            Opcode::Equals => 8,
+            Opcode::RBO => 9,
+            Opcode::ProgramEnd => 99,
+            Opcode::Custom(custom) => custom.code
+        }
+    }
+
+    /// Index of the operand this instruction writes to, if any. Custom opcodes may
+    /// write more than one; this is the first.
+    pub fn write_operand(&self) -> Option<usize> {
+        match *self {
+            Opcode::Add|Opcode::Mult|Opcode::LessThan|Opcode::Equals => Some(2),
+            Opcode::Input => Some(0),
+            Opcode::Custom(custom) => (0..custom.arity).find(|index| custom.is_write_operand(*index)),
+            _ => None
+        }
+    }
+
+    pub fn is_write_operand(&self, index : usize) -> bool {
+        match *self {
+            Opcode::Custom(custom) => custom.is_write_operand(index),
+            _ => self.write_operand() == Some(index)
+        }
+    }
+
+    /// Indices of every operand this instruction writes to.
+    pub fn write_operands(&self) -> Vec<usize> {
+        (0..self.get_size() - 1).filter(|index| self.is_write_operand(*index)).collect()
+    }
+
     pub fn get_size(&self) -> usize {
         match *self {
             Opcode::Add => 4,
@@ -85,11 +207,12 @@ impl Opcode {
             Opcode::LessThan => 4,
             Opcode::Equals => 4,
             Opcode::RBO => 2,
-            Opcode::ProgramEnd => 1
+            Opcode::ProgramEnd => 1,
+            Opcode::Custom(custom) => custom.arity + 1
         }
     }
 
-    pub fn disassemble(&self, args : &[OpcodeArg]) -> String {
+    pub fn disassemble<W : Word>(&self, args : &[OpcodeArg<W>]) -> String {
         let arg_string = args.iter().fold(String::new(), |arg_string, arg| {
             format!("{}, ({}, (&{}, MODE:{}))", arg_string, arg.value, arg.address, arg.parameter_mode)
         });
@@ -98,143 +221,449 @@ impl Opcode {
     }
 }
 
-pub struct IntCodeMachine{
-    pub program : Vec<i64>,
-    pub output : Vec<i64>,
-    pub is_halted: bool,
-    pub program_complete : bool,
+pub struct IntCodeMachine<W : Word = i64>{
+    pub memory : Memory<W>,
+    pub output : Vec<W>,
 
-    std_input: bool,
-    input : Vec<i64>,
+    state : MachineState,
+    input : VecDeque<W>,
+    input_source : Option<Box<dyn InputSource<W>>>,
+    output_sink : Option<Box<dyn OutputSink<W>>>,
     program_counter : usize,
-    relative_base_offset : usize,
-    output_dasm : bool
+    relative_base_offset : i64,
+    output_dasm : bool,
+    cycles : u64,
+    cycle_budget : Option<u64>,
+    total_budget : Option<u64>,
+    // (pc, relative base, memory fingerprint) seen since the last I/O.
+    seen_states : Option<HashSet<(usize, i64, u64)>>,
+    profile : Option<Profile>,
+    breakpoints : Breakpoints,
+    last_break : Option<BreakReason<W>>,
+    // Pc of a before-instruction break we just stopped at, so resuming doesn't stop there again.
+    resume_past : Option<usize>,
+    history : Option<History<W>>,
+    engine : Engine,
+    code_monitor : Option<CodeMonitor<W>>,
+    arithmetic : Arithmetic,
+    registry : Registry<W>,
+    // What the last custom instruction did, for its undo record.
+    host_effects : Option<HostEffects<W>>
 }
 
-impl IntCodeMachine {
-    pub fn new(program : &Vec<i64>, input : Option<&Vec<i64>>) -> Self {
-        let mut input = match input {
-            Some(given_input) => given_input.clone(),
-            _ => vec![]
+impl<W : Word> IntCodeMachine<W> {
+    pub fn new(program : &[W], input : Option<&Vec<W>>) -> Self {
+        let input = match input {
+            Some(given_input) => given_input.iter().cloned().collect(),
+            _ => VecDeque::new()
         };
 
-        // TODO: sick interpreter hack. Move this to a proper memory map.
-        let mut program_space : Vec<i64> = vec![0; program.len()*10];
-        let mut program = program.clone();
-
-        program.append(&mut program_space);
-
-        input.reverse();
-
         Self {
-            program,
+            memory : Memory::new(program),
             input,
             output : vec![],
             program_counter : 0,
-            std_input: false,
-            is_halted : false,
-            program_complete : false,
+            state : MachineState::Ready,
+            input_source : None,
+            output_sink : None,
             relative_base_offset : 0,
-            output_dasm : false
+            output_dasm : false,
+            cycles : 0,
+            cycle_budget : None,
+            total_budget : None,
+            seen_states : None,
+            profile : None,
+            breakpoints : Breakpoints::new(),
+            last_break : None,
+            resume_past : None,
+            history : None,
+            engine : Engine::Interpreter,
+            code_monitor : None,
+            arithmetic : Arithmetic::Wrapping,
+            registry : Registry::new(),
+            host_effects : None
+        }
+    }
+
+    /// A fresh machine (no devices attached) in exactly the state `snapshot` recorded.
+    pub fn from_snapshot(snapshot : &Snapshot<W>) -> Self {
+        let mut machine = IntCodeMachine::new(&[], None);
+
+        machine.restore(snapshot);
+        machine
+    }
+
+    /// A fresh machine with the image's program, starting at its entry point with its
+    /// relative base.
+    pub fn from_image(image : &ProgramImage<W>) -> Self {
+        let mut machine = IntCodeMachine::new(&image.program, None);
+
+        machine.program_counter = image.entry_point.unwrap_or(0);
+        machine.relative_base_offset = image.relative_base;
+        machine
+    }
+
+    pub fn load_image<P : AsRef<std::path::Path>>(path : P) -> Result<Self, ImageError> {
+        ProgramImage::load_from_file(path).map(|image| Self::from_image(&image))
+    }
+
+    pub fn snapshot(&self) -> Snapshot<W> {
+        Snapshot {
+            memory : self.memory.clone(),
+            program_counter : self.program_counter,
+            relative_base_offset : self.relative_base_offset,
+            input : self.input.clone(),
+            output : self.output.clone(),
+            state : self.state.clone()
+        }
+    }
+
+    /// Rewind to `snapshot`. Attached input sources and output sinks are kept.
+    pub fn restore(&mut self, snapshot : &Snapshot<W>){
+        self.memory = snapshot.memory.clone();
+        self.program_counter = snapshot.program_counter;
+        self.relative_base_offset = snapshot.relative_base_offset;
+        self.input = snapshot.input.clone();
+        self.output = snapshot.output.clone();
+        self.state = snapshot.state.clone();
+
+        // The undo log describes how we got to the old state, not the restored one.
+        if self.history.is_some() {
+            self.history = Some(History::new(self.cycles));
         }
     }
 
+    pub fn state(&self) -> &MachineState {
+        &self.state
+    }
+
+    pub fn program_counter(&self) -> usize {
+        self.program_counter
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base_offset
+    }
+
     pub fn set_debug_mode(&mut self){
         self.output_dasm = true;
     }
 
-    pub fn read_file_into_program(program_name : &str) -> Vec<i64>{
-        read_input_file(program_name)
-            .split(",")
-            .map( |x| x.parse::<i64>())
-            .filter_map(|x| x.ok())
-            .collect()
+    pub fn set_arithmetic(&mut self, arithmetic : Arithmetic){
+        self.arithmetic = arithmetic;
+    }
+
+    /// Custom opcodes this machine decodes, lists and executes.
+    pub fn registry(&self) -> &Registry<W> {
+        &self.registry
+    }
+
+    pub fn registry_mut(&mut self) -> &mut Registry<W> {
+        &mut self.registry
+    }
+
+    pub fn set_engine(&mut self, engine : Engine){
+        self.engine = engine;
+
+        if engine == Engine::Interpreter {
+            self.memory.disable_decode_cache();
+        }
+    }
+
+    /// Limit how many instructions a single `run`/`run_until_*` call may execute.
+    pub fn set_cycle_budget(&mut self, budget : Option<u64>){
+        self.cycle_budget = budget;
+    }
+
+    /// Limit how many instructions the machine may execute over its whole lifetime.
+    pub fn set_total_budget(&mut self, budget : Option<u64>){
+        self.total_budget = budget;
+    }
+
+    /// Fault with `InfiniteLoop` when the machine returns to an exact earlier state (same
+    /// pc, relative base and memory) without any I/O in between. Memory is compared by
+    /// fingerprint, so this costs a hash-set insert per instruction.
+    pub fn set_loop_detection(&mut self, enabled : bool){
+        self.seen_states = match enabled {
+            true => Some(HashSet::new()),
+            false => None
+        };
+    }
+
+    /// Count executions, operand modes, memory traffic and branch outcomes while running.
+    /// Turning profiling on again starts a fresh profile.
+    pub fn set_profiling(&mut self, enabled : bool){
+        self.profile = match enabled {
+            true => Some(Profile::new()),
+            false => None
+        };
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
     }
 
-    fn get_input_from_stdin() -> Result<i64, &'static str> {
-        let mut input = String::new();
-        print!("Input: ");
-        io::stdout().flush();
-        if let Err(_) = io::stdin().read_line(&mut input){
-            return Err("Failed to extract user input");
+    pub fn breakpoints(&self) -> &Breakpoints {
+        &self.breakpoints
+    }
+
+    /// Breakpoints, watchpoints and opcode/I/O breaks honoured by `run` and the
+    /// `run_until_*` drivers.
+    pub fn breakpoints_mut(&mut self) -> &mut Breakpoints {
+        &mut self.breakpoints
+    }
+
+    /// What stopped the most recent run early, if anything did.
+    pub fn last_break(&self) -> Option<BreakReason<W>> {
+        self.last_break.clone()
+    }
+
+    /// Watch for writes to cells that have already been executed as instructions or
+    /// operands. Turning monitoring on again forgets what was executed before.
+    pub fn set_code_monitoring(&mut self, enabled : bool){
+        self.code_monitor = match enabled {
+            true => Some(CodeMonitor::new()),
+            false => None
+        };
+    }
+
+    pub fn code_monitor(&self) -> Option<&CodeMonitor<W>> {
+        self.code_monitor.as_ref()
+    }
+
+    /// Keep an undo log of every executed instruction so the machine can be stepped and
+    /// rewound backwards. Output already handed to an attached sink can't be taken back.
+    /// Turning recording on again starts a fresh log.
+    pub fn set_recording(&mut self, enabled : bool){
+        self.history = match enabled {
+            true => Some(History::new(self.cycles)),
+            false => None
+        };
+    }
+
+    pub fn history(&self) -> Option<&History<W>> {
+        self.history.as_ref()
+    }
+
+    /// Undo the most recently executed instruction. Returns false if there's nothing
+    /// recorded to undo. Profiling counters are not rolled back.
+    pub fn step_back(&mut self) -> bool {
+        let record = match self.history.as_mut().and_then(|history| history.pop()) {
+            Some(record) => record,
+            None => return false
+        };
+
+        for (address, value) in record.writes.into_iter().rev() {
+            self.memory.write(address, value);
         }
-        if let Ok(parse_input) = input.trim().parse::<i64>(){
-            Ok(parse_input)
-        }else{
-            return Err("Failed to parse user input");
+
+        for value in record.inputs.into_iter().rev() {
+            self.input.push_front(value);
+        }
+
+        let kept = self.output.len().saturating_sub(record.outputs);
+        self.output.truncate(kept);
+
+        self.program_counter = record.program_counter;
+        self.relative_base_offset = record.relative_base_offset;
+        self.state = record.state;
+        self.cycles -= 1;
+        self.resume_past = None;
+        self.last_break = None;
+
+        if let Some(seen) = self.seen_states.as_mut() {
+            seen.clear();
         }
+
+        true
     }
 
+    /// Step back to just before the most recent recorded write to `address`, returning the
+    /// cycle it happened in. If there is no such write the machine doesn't move.
+    pub fn run_back_to_write(&mut self, address : usize) -> Option<u64> {
+        let cycle = self.history.as_ref()?.last_write(address)?;
 
-    fn is_valid_pc_state(&self) -> Result<(), &'static str>{
-        if self.program_counter > 0 || self.program_counter < self.program.len(){
-            return Ok(())
+        self.rewind_to(cycle);
+        Some(cycle)
+    }
+
+    /// Step back until `cycles()` is `cycle`. Returns false, without moving, if that
+    /// cycle is in the future or before recording started.
+    pub fn rewind_to(&mut self, cycle : u64) -> bool {
+        match self.history.as_ref() {
+            Some(history) if history.start() <= cycle && cycle <= self.cycles => {},
+            _ => return false
+        }
+
+        while self.cycles > cycle {
+            self.step_back();
         }
 
-        Err("Program in invalid state")
+        true
     }
 
-    fn get_input(&mut self) -> Result<i64, &'static str>{
-        match self.input.pop() {
-            Some(result) => Ok(result),
-            _ => {
-                if self.std_input {
-                    return IntCodeMachine::get_input_from_stdin()
-                }
+    /// Instructions executed so far.
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
 
-                // Hack: indicate wait needs to happen
-                self.is_halted = true;
-                Ok(0)
-            }
+    /// Load the program in resources/`program_name`. See `loader::load_str` for the format.
+    pub fn read_file_into_program(program_name : &str) -> Result<Vec<W>, LoadError>{
+        loader::load_path(resource_path(program_name))
+    }
+
+    /// Pull input from `source` once the values passed to `new`/`send_input` run out.
+    pub fn set_input_source<S : InputSource<W> + 'static>(&mut self, source : S){
+        self.input_source = Some(Box::new(source));
+    }
+
+    /// Send output to `sink` instead of collecting it in `output`.
+    pub fn set_output_sink<S : OutputSink<W> + 'static>(&mut self, sink : S){
+        self.output_sink = Some(Box::new(sink));
+    }
+
+    fn io_failure(&self, err : std::io::Error) -> IntcodeError {
+        IntcodeError::IoFailure { pc : self.program_counter, message : err.to_string() }
+    }
+
+    fn address_of(&self, value : i64) -> Result<usize, IntcodeError>{
+        if value < 0 {
+            return Err(IntcodeError::NegativeAddress { pc : self.program_counter, address : value });
         }
+
+        Ok(value as usize)
     }
 
-    fn set_std_input(&mut self, receive_input : bool){
-        self.std_input = receive_input;
+    // A word too wide for an i64 can't be an address, whichever way it points.
+    fn address_out_of_range(&self, value : &W) -> IntcodeError {
+        match value.saturating_i64() {
+            address if address < 0 => IntcodeError::NegativeAddress { pc : self.program_counter, address },
+            _ => IntcodeError::AddressOutOfRange { pc : self.program_counter, address : value.to_string() }
+        }
     }
 
-    fn compute(opcode : &Opcode, args : &Vec<OpcodeArg>) -> Option<i64> {
-        match opcode {
-            Opcode::Add => Some(args[0].value+args[1].value),
-            Opcode::Mult => Some(args[0].value*args[1].value),
-            _ => None
+    fn to_address(&self, value : &W) -> Result<usize, IntcodeError>{
+        match value.to_i64() {
+            Some(address) => self.address_of(address),
+            None => Err(self.address_out_of_range(value))
+        }
+    }
+
+    fn relative_address(&self, operand : &W) -> Result<i64, IntcodeError>{
+        match operand.to_i64() {
+            Some(offset) => self.checked(offset.add(&self.relative_base_offset, self.arithmetic)),
+            None => Err(self.address_out_of_range(operand))
+        }
+    }
+
+    fn adjusted_relative_base(&self, adjustment : &W) -> Result<i64, IntcodeError>{
+        let adjustment = adjustment.to_i64().and_then(|adjustment| self.relative_base_offset.add(&adjustment, self.arithmetic));
+
+        self.checked(adjustment)
+    }
+
+    // Where a write through `target` lands.
+    fn write_address(&self, target : &OpcodeArg<W>) -> Result<usize, IntcodeError>{
+        if target.parameter_mode == 1 {
+            return Err(IntcodeError::WriteInImmediateMode {
+                pc : self.program_counter,
+                instruction : self.memory.read(self.program_counter).saturating_i64()
+            });
         }
+
+        self.address_of(target.address)
+    }
+
+    fn write(&mut self, target : &OpcodeArg<W>, value : W) -> Result<(), IntcodeError>{
+        let address = self.write_address(target)?;
+        self.memory.write(address, value);
+
+        Ok(())
     }
 
-    fn extract_args(&mut self, opcode : &Opcode, parameter_mode : &i64) -> Result<Vec<OpcodeArg>, &'static str> {
-        let mut args : Vec<OpcodeArg> = vec![];
-        let mut mode : i64 = parameter_mode.clone();
-        let arg_range = self.program_counter+1..self.program_counter+opcode.get_size();
+    // None means the machine has to wait for more input.
+    fn get_input(&mut self) -> Result<Option<W>, IntcodeError>{
+        if let Some(result) = self.input.pop_front() {
+            return Ok(Some(result));
+        }
+
+        match self.input_source.as_mut().map(|source| source.next_input()) {
+            Some(Err(err)) => Err(self.io_failure(err)),
+            Some(Ok(result)) => Ok(result),
+            None => Ok(None)
+        }
+    }
 
-        if opcode == &Opcode::ProgramEnd{
-            return Ok(args);
+    fn put_output(&mut self, value : W) -> Result<(), IntcodeError>{
+        match self.output_sink.as_mut() {
+            Some(sink) => sink.write_output(value).map_err(|err| self.io_failure(err)),
+            None => {
+                self.output.push(value);
+                Ok(())
+            }
         }
+    }
+
+    // Turn a `None` from the arithmetic policy into an overflow fault.
+    fn checked<T>(&self, result : Option<T>) -> Result<T, IntcodeError>{
+        result.ok_or_else(|| IntcodeError::ArithmeticOverflow {
+            pc : self.program_counter,
+            instruction : self.memory.read(self.program_counter).saturating_i64()
+        })
+    }
+
+    fn compute(&self, opcode : &Opcode, args : &[OpcodeArg<W>]) -> Result<Option<W>, IntcodeError> {
+        let result = match opcode {
+            Opcode::Add => args[0].value.add(&args[1].value, self.arithmetic),
+            Opcode::Mult => args[0].value.mul(&args[1].value, self.arithmetic),
+            _ => return Ok(None)
+        };
+
+        self.checked(result).map(Some)
+    }
 
-        for value in self.program[arg_range].iter(){
+    fn extract_args(&mut self, opcode : &Opcode, parameter_mode : &i64) -> Result<Vec<OpcodeArg<W>>, IntcodeError> {
+        let mut args : Vec<OpcodeArg<W>> = vec![];
+        let mut mode : i64 = *parameter_mode;
+
+        for arg_address in self.program_counter+1..self.program_counter+opcode.get_size(){
+            let value = self.memory.read(arg_address);
             let next_mode = mode % 10;
             let next_arg = match next_mode {
-                0 => OpcodeArg::new(next_mode, self.program[*value as usize], *value),
-                1 => OpcodeArg::new(next_mode, *value, *value),
+                0 => {
+                    let address = self.to_address(&value)?;
+
+                    OpcodeArg::new(next_mode, self.memory.read(address), address as i64)
+                },
+                1 => {
+                    let address = value.saturating_i64();
+
+                    OpcodeArg::new(next_mode, value, address)
+                },
                 2 => {
-                    let relative_offset = (*value + (self.relative_base_offset as (i64))) as usize;
+                    let relative_offset = self.relative_address(&value)?;
 
-                    OpcodeArg::new(next_mode, self.program[relative_offset], relative_offset as i64)
+                    OpcodeArg::new(next_mode, self.memory.read(self.address_of(relative_offset)?), relative_offset)
                 },
-                _ => return Err("Invalid parameter mode")
+                _ => return Err(IntcodeError::InvalidParameterMode {
+                    pc : self.program_counter,
+                    instruction : self.memory.read(self.program_counter).saturating_i64(),
+                    mode : next_mode
+                })
             };
 
             args.push(next_arg);
-            mode = mode / 10;
-
+            mode /= 10;
         }
 
-        return Ok(args);
+        Ok(args)
     }
 
-    fn run_cycle(&mut self) -> Result<(Opcode, Vec<OpcodeArg>), &'static str>{
-        let (opcode, parameter_mode) = Opcode::new(self.program[self.program_counter])?;
+    fn run_cycle(&mut self) -> Result<(Opcode, Vec<OpcodeArg<W>>), IntcodeError>{
+        let (opcode, parameter_mode) = self.registry.decode(self.memory.read(self.program_counter))
+            .map_err(|err| err.at(self.program_counter))?;
         let args = self.extract_args(&opcode, &parameter_mode)?;
-        let result = IntCodeMachine::compute(&opcode, &args);
+        let result = self.compute(&opcode, &args)?;
 
         if self.output_dasm {
             println!("\t{}", opcode.disassemble(&args));
@@ -242,43 +671,72 @@ impl IntCodeMachine {
 
         match opcode {
             Opcode::Add|Opcode::Mult => {
-                self.program[args[2].address as usize] = result.unwrap();
+                self.write(&args[2], result.unwrap())?;
             }
             Opcode::Input => {
-                let input_result = self.get_input()?;
-                // Hack
-                if self.is_halted {
-                    return Ok((opcode, args));
+                match self.get_input()? {
+                    Some(input_result) => self.write(&args[0], input_result)?,
+                    None => {
+                        self.state = MachineState::AwaitingInput;
+                        return Ok((opcode, args));
+                    }
                 }
-
-                self.program[args[0].address as usize] = input_result;
             },
             Opcode::Output => {
-                self.output.push(args[0].value);
+                self.put_output(args[0].value.clone())?;
             },
             Opcode::JumpIfTrue => {
-                if args[0].value != 0 {
-                    self.program_counter = args[1].value as usize;
+                if !args[0].value.is_zero() {
+                    self.program_counter = self.to_address(&args[1].value)?;
                     return Ok((opcode, args));
                 }
             },
             Opcode::JumpIfFalse => {
-                if args[0].value == 0 {
-                    self.program_counter = args[1].value as usize;
+                if args[0].value.is_zero() {
+                    self.program_counter = self.to_address(&args[1].value)?;
                     return Ok((opcode, args));
                 }
             },
             Opcode::LessThan => {
-                self.program[args[2].address as usize] = (args[0].value < args[1].value) as i64;
+                self.write(&args[2], W::from_i64((args[0].value < args[1].value) as i64))?;
             },
             Opcode::Equals => {
-                self.program[args[2].address as usize] = (args[0].value == args[1].value) as i64;
+                self.write(&args[2], W::from_i64((args[0].value == args[1].value) as i64))?;
             },
             Opcode::RBO => {
-                let next = self.relative_base_offset as i64 + args[0].value;
-                self.relative_base_offset = next as usize;
+                self.relative_base_offset = self.adjusted_relative_base(&args[0].value)?;
+            }
+            Opcode::ProgramEnd => {
+                self.state = MachineState::Halted;
+                return Ok((opcode, args));
+            }
+            Opcode::Custom(custom) => {
+                let callback = self.registry.callback(custom.code)
+                    .ok_or(IntcodeError::InvalidOpcode { pc : self.program_counter, instruction : custom.code })?;
+                let mut host = Host::new(self, custom, &args);
+
+                callback(&mut host)?;
+
+                let effects = host.into_effects();
+                let (jump, halted, blocked) = (effects.jump, effects.halted, effects.blocked);
+
+                self.host_effects = Some(effects);
+
+                if blocked {
+                    self.state = MachineState::AwaitingInput;
+                    return Ok((opcode, args));
+                }
+
+                if halted {
+                    self.state = MachineState::Halted;
+                    return Ok((opcode, args));
+                }
+
+                if let Some(address) = jump {
+                    self.program_counter = address;
+                    return Ok((opcode, args));
+                }
             }
-            Opcode::ProgramEnd => return Ok((opcode, args))
         }
 
         self.program_counter += opcode.get_size();
@@ -286,61 +744,312 @@ impl IntCodeMachine {
         Ok((opcode, args))
     }
 
-    pub fn send_input(&mut self, input : i64) -> Result<(), &'static str>{
-        if self.program_complete {
-            return Err("Attempt to send input to program that is no longer running.");
+    fn illegal_transition(&self, to : MachineState) -> IntcodeError {
+        IntcodeError::IllegalTransition {
+            pc : self.program_counter,
+            from : Box::new(self.state.clone()),
+            to : Box::new(to)
+        }
+    }
+
+    // Move into Running, refusing to resume a machine that has already stopped for good.
+    fn resume(&mut self) -> Result<(), IntcodeError>{
+        if self.state.is_terminal() {
+            return Err(self.illegal_transition(MachineState::Running));
         }
 
-        self.input.push(input);
-        self.is_halted = false;
+        self.state = MachineState::Running;
+
+        Ok(())
+    }
+
+    /// Queue an input value without executing anything.
+    pub fn push_input(&mut self, input : W) -> Result<(), IntcodeError>{
+        if self.state.is_terminal() {
+            return Err(self.illegal_transition(MachineState::AwaitingInput));
+        }
+
+        self.input.push_back(input);
+
+        Ok(())
+    }
+
+    pub fn push_inputs(&mut self, inputs : &[W]) -> Result<(), IntcodeError>{
+        inputs.iter().try_for_each(|input| self.push_input(input.clone()))
+    }
+
+    pub fn send_input(&mut self, input : W) -> Result<MachineState, IntcodeError>{
+        if self.state.is_terminal() {
+            return Err(self.illegal_transition(MachineState::Running));
+        }
+
+        self.input.push_back(input);
 
         self.run()
     }
 
-    pub fn run(&mut self) -> Result<(), &'static str>{
+    fn budget_exhausted(&self) -> IntcodeError {
+        IntcodeError::BudgetExhausted { pc : self.program_counter, cycles : self.cycles }
+    }
+
+    // One instruction with state bookkeeping: faults are recorded before being returned.
+    // Running out of budget is not a fault; the machine stays resumable.
+    fn execute(&mut self) -> Result<(Opcode, Vec<OpcodeArg<W>>), IntcodeError>{
+        let prior_state = self.state.clone();
+
+        self.resume()?;
+
+        if self.total_budget.is_some_and(|budget| self.cycles >= budget) {
+            return Err(self.budget_exhausted());
+        }
+
+        let address = self.program_counter;
+        let relative_base_offset = self.relative_base_offset;
+        let current = (address, relative_base_offset, self.memory.fingerprint());
+
+        if let Some(seen) = self.seen_states.as_mut() {
+            if !seen.insert(current) {
+                let err = IntcodeError::InfiniteLoop { pc : self.program_counter, cycles : self.cycles };
+
+                self.state = MachineState::Faulted(err.clone());
+                return Err(err);
+            }
+        }
+
+        let (opcode, args) = self.run_cycle().inspect_err(|err| {
+            self.state = MachineState::Faulted(err.clone());
+        })?;
 
-        loop{
-            let (last_opcode,_) = self.run_cycle()?;
+        if self.state != MachineState::AwaitingInput {
+            self.cycles += 1;
 
-            self.is_valid_pc_state()?;
+            if let Some(profile) = self.profile.as_mut() {
+                profile.record(address, opcode, &args);
+            }
+
+            if let Some(monitor) = self.code_monitor.as_mut() {
+                let memory = &self.memory;
+                let writes = match (opcode, &self.host_effects) {
+                    (Opcode::Custom(_), Some(effects)) => effects.writes.clone(),
+                    _ => opcode.write_operands().into_iter().map(|index| {
+                        let target = args[index].address as usize;
+
+                        (target, args[index].value.clone(), memory.read(target))
+                    }).collect()
+                };
 
-            if self.is_halted {
-                break;
+                monitor.record(address, opcode.get_size(), writes);
             }
 
-            if last_opcode == Opcode::ProgramEnd{
-                self.program_complete = true;
-                self.is_halted = true;
-                break;
+            self.resume_past = None;
+
+            let record = self.history.as_ref()
+                .map(|_| self.undo_record(address, relative_base_offset, prior_state, opcode, &args));
+
+            if let (Some(history), Some(record)) = (self.history.as_mut(), record) {
+                history.push(record);
             }
         }
 
-        Ok(())
+        // Input and output (including blocking on input) change what happens next, so
+        // earlier states no longer prove anything.
+        if opcode == Opcode::Input || opcode == Opcode::Output || self.host_io(opcode) {
+            if let Some(seen) = self.seen_states.as_mut() {
+                seen.clear();
+            }
+        }
+
+        Ok((opcode, args))
     }
 
-    // Todo: Now that there's branching, need to sweep to cover all reachable program space.
-    pub fn disassemble(&mut self) -> Result<String, &'static str>{
-        let mut assembly = String::new();
+    // Called after the instruction completed; write operands still hold the value they saw
+    // before being overwritten. Custom instructions only count the writes they made.
+    fn undo_record(&self, address : usize, relative_base_offset : i64, state : MachineState, opcode : Opcode, args : &[OpcodeArg<W>]) -> UndoRecord<W> {
+        let writes : Vec<(usize, W)> = match (opcode, &self.host_effects) {
+            (Opcode::Custom(_), Some(effects)) => effects.writes.iter()
+                .map(|(target, old, _)| (*target, old.clone()))
+                .collect(),
+            _ => opcode.write_operands().into_iter()
+                .map(|index| (args[index].address as usize, args[index].value.clone()))
+                .collect()
+        };
+        let (inputs, outputs) = match (opcode, &self.host_effects) {
+            (Opcode::Input, _) => (vec![self.memory.read(writes[0].0)], 0),
+            (Opcode::Output, _) => (vec![], self.output_sink.is_none() as usize),
+            (Opcode::Custom(_), Some(effects)) => (effects.inputs.clone(), effects.outputs),
+            _ => (vec![], 0)
+        };
+
+        UndoRecord {
+            program_counter : address,
+            relative_base_offset,
+            state,
+            writes,
+            inputs,
+            outputs
+        }
+    }
+
+    fn break_before(&self) -> Option<BreakReason<W>> {
+        if self.breakpoints.is_empty() || self.resume_past == Some(self.program_counter) {
+            return None;
+        }
+
+        let opcode = self.registry.decode(self.memory.read(self.program_counter)).ok().map(|(opcode, _)| opcode);
+
+        self.breakpoints.before(self.program_counter, opcode)
+    }
+
+    fn break_after(&self, address : usize, opcode : Opcode, args : &[OpcodeArg<W>]) -> Option<BreakReason<W>> {
+        if self.breakpoints.is_empty() || self.state == MachineState::AwaitingInput {
+            return None;
+        }
+
+        let input = match opcode {
+            Opcode::Input => Some(self.memory.read(args[0].address as usize)),
+            _ => None
+        };
+
+        self.breakpoints.after(address, opcode, args, input, self.host_io(opcode))
+    }
+
+    // Whether `opcode`, just executed, was a custom instruction that did I/O.
+    fn host_io(&self, opcode : Opcode) -> bool {
+        matches!(opcode, Opcode::Custom(_)) && self.host_effects.as_ref().is_some_and(|effects| effects.io)
+    }
+
+    // Shared driver loop: execute until `stop` produces a result, enforcing the per-run budget
+    // and handing breaks to `interrupted`.
+    fn drive<T, F, B>(&mut self, mut stop : F, interrupted : B) -> Result<T, IntcodeError>
+        where F : FnMut(&Self, &Opcode, &[OpcodeArg<W>]) -> Option<T>,
+              B : Fn(&Self, BreakReason<W>) -> T
+    {
+        let mut executed = 0;
+
+        self.last_break = None;
 
         loop {
-            let (last_opcode, args) = self.run_cycle()?;
+            if self.cycle_budget.is_some_and(|budget| executed >= budget) {
+                return Err(self.budget_exhausted());
+            }
+
+            if let Some(reason) = self.break_before() {
+                self.resume_past = Some(self.program_counter);
+                self.last_break = Some(reason.clone());
+                return Ok(interrupted(self, reason));
+            }
+
+            let address = self.program_counter;
+            let (opcode, args) = self.execute()?;
+            executed += 1;
 
-            assembly = format!("{}\n{}", assembly, last_opcode.disassemble(&args));
+            if let Some(reason) = self.break_after(address, opcode, &args) {
+                self.last_break = Some(reason.clone());
+                return Ok(interrupted(self, reason));
+            }
 
-            if last_opcode == Opcode::ProgramEnd {
-                break;
+            if let Some(result) = stop(self, &opcode, &args) {
+                return Ok(result);
             }
         }
+    }
+
+    fn stop_reason(&self) -> Option<StopReason<W>>{
+        match self.state {
+            MachineState::AwaitingInput => Some(StopReason::AwaitingInput),
+            MachineState::Halted => Some(StopReason::Halted),
+            _ => None
+        }
+    }
+
+    /// Execute a single instruction, ignoring breakpoints, and report what was executed
+    /// and the state the machine is left in.
+    pub fn step(&mut self) -> Result<Step<W>, IntcodeError>{
+        let address = self.program_counter;
+        let (opcode, args) = self.execute()?;
+
+        Ok(Step { address, opcode, args, state : self.state.clone() })
+    }
+
+    /// Execute until the next output instruction, or until the program blocks or halts first.
+    /// The value is still delivered to the output buffer/sink as usual.
+    pub fn run_until_output(&mut self) -> Result<StopReason<W>, IntcodeError>{
+        if self.can_predecode() {
+            return self.run_predecoded(true);
+        }
+
+        self.drive(|machine, opcode, args| {
+            machine.stop_reason().or(match opcode {
+                Opcode::Output => Some(StopReason::Output(args[0].value.clone())),
+                _ => None
+            })
+        }, |_, reason| StopReason::Break(reason))
+    }
+
+    /// Execute until the program blocks waiting for input, or halts.
+    pub fn run_until_input(&mut self) -> Result<StopReason<W>, IntcodeError>{
+        if self.can_predecode() {
+            return self.run_predecoded(false);
+        }
+
+        self.drive(|machine, _, _| machine.stop_reason(), |_, reason| StopReason::Break(reason))
+    }
+
+    /// Execute until the program halts. Running out of input is an error here, but the
+    /// machine is left in `AwaitingInput` and can be resumed once more input is queued.
+    pub fn run_until_halt(&mut self) -> Result<StopReason<W>, IntcodeError>{
+        match self.run_until_input()? {
+            StopReason::AwaitingInput => Err(IntcodeError::InputExhausted { pc : self.program_counter }),
+            reason => Ok(reason)
+        }
+    }
+
+    /// Execute until the program halts, faults, or blocks waiting for input. A break leaves
+    /// the machine `Running`; `last_break` says why it stopped.
+    pub fn run(&mut self) -> Result<MachineState, IntcodeError>{
+        if self.can_predecode() {
+            return self.run_predecoded(false).map(|_| self.state.clone());
+        }
+
+        self.drive(|machine, _, _| match machine.state {
+            MachineState::Running => None,
+            ref state => Some(state.clone())
+        }, |machine, _| machine.state.clone())
+    }
+
+    /// Static listing of everything reachable in the program image. Nothing is executed.
+    pub fn disassemble(&self) -> Listing<W> {
+        disassembler::disassemble_with(self.memory.image(), &self.registry)
+    }
+
+    /// Run the program to completion, recording each executed instruction with the operand
+    /// values it actually saw. Blocking on input or hitting a breakpoint ends the trace
+    /// early; budgets apply as they do for `run`.
+    pub fn trace(&mut self) -> Result<String, IntcodeError>{
+        let mut assembly = String::new();
+
+        self.drive(|machine, opcode, args| {
+            if machine.state == MachineState::AwaitingInput {
+                return Some(());
+            }
+
+            assembly = format!("{}\n{}", assembly, opcode.disassemble(args));
+
+            match machine.state {
+                MachineState::Running => None,
+                _ => Some(())
+            }
+        }, |_, _| ())?;
 
         Ok(assembly)
     }
 
     // Run a program an mutate the input array
-    pub fn run_program(program : &mut Vec<i64>, args : Option<&Vec<i64>>) -> Result<Vec<i64>, &'static str>{
+    pub fn run_program(program : &mut Vec<W>, args : Option<&Vec<W>>) -> Result<Vec<W>, IntcodeError>{
         let mut int_machine = IntCodeMachine::new(program, args);
 
-        int_machine.run();
-        *program = int_machine.program;
+        int_machine.run_until_halt()?;
+        *program = int_machine.memory.image().to_vec();
 
         Ok(int_machine.output.clone())
     }
@@ -354,7 +1063,7 @@ mod intcode_tests{
     #[test]
     fn process_add_opcode() {
         match  Opcode::new(1){
-            Ok((Opcode::Add, 0)) => assert!(true),
+            Ok((Opcode::Add, 0)) => {},
             _ => panic!()
         }
     }
@@ -362,7 +1071,7 @@ mod intcode_tests{
     #[test]
     fn process_mult_opcode(){
         match  Opcode::new(2){
-            Ok((Opcode::Mult, 0)) => assert!(true),
+            Ok((Opcode::Mult, 0)) => {},
             _ => panic!()
         }
     }
@@ -421,18 +1130,14 @@ mod intcode_tests{
         let program = vec![3,1,1,1,2,1,99];
         let mut machine = IntCodeMachine::new(&program, None);
 
-        machine.run().unwrap();
-
-        assert!(!machine.program_complete);
-        assert!(machine.is_halted);
-
-        machine.send_input(9).unwrap();
+        assert_eq!(machine.run().unwrap(), MachineState::AwaitingInput);
+        assert_eq!(machine.state(), &MachineState::AwaitingInput);
 
-        assert!(machine.program_complete);
-        assert!(machine.is_halted);
+        assert_eq!(machine.send_input(9).unwrap(), MachineState::Halted);
+        assert_eq!(machine.state(), &MachineState::Halted);
 
         assert_eq!(
-            machine.program[..7],
+            machine.memory.image()[..7],
             [3,10,1,1,2,1,99]
         )
     }
@@ -457,7 +1162,7 @@ mod intcode_tests{
 
         let mut int_machine = IntCodeMachine::new(&program, Some(&input));
 
-        let dasm = int_machine.disassemble().unwrap();
+        let dasm = int_machine.trace().unwrap();
 
         assert_eq!(dasm,
 "
@@ -469,9 +1174,23 @@ HALT"
         )
     }
 
+    #[test]
+    fn trace_stops_when_input_runs_out(){
+        let mut machine : IntCodeMachine = IntCodeMachine::new(&[1101,1,2,5,3,0,99], None);
+
+        assert_eq!(machine.trace().unwrap(), "\nADD, (1, (&1, MODE:1)), (2, (&2, MODE:1)), (0, (&5, MODE:0))");
+        assert_eq!(machine.state(), &MachineState::AwaitingInput);
+
+        let mut machine : IntCodeMachine = IntCodeMachine::new(&[1105,1,0], None);
+
+        machine.set_cycle_budget(Some(5));
+
+        assert_eq!(machine.trace(), Err(IntcodeError::BudgetExhausted { pc : 0, cycles : 5 }));
+    }
+
     #[test]
     fn large_number(){
-        let program = vec![1102,34915192,34915192,7,4,7,99,0];
+        let program : Vec<i64> = vec![1102,34915192,34915192,7,4,7,99,0];
         let mut machine = IntCodeMachine::new(&program, None);
 
         machine.run().unwrap();
@@ -481,7 +1200,7 @@ HALT"
             1_219_070_632_396_864
         );
 
-        let program = vec![104,1125899906842624,99];
+        let program : Vec<i64> = vec![104,1125899906842624,99];
         let mut machine = IntCodeMachine::new(&program, None);
 
         machine.run().unwrap();
@@ -504,4 +1223,217 @@ HALT"
             vec![109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99]
         )
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn write_past_program_image(){
+        let program = vec![1101,6,7,1000000,4,1000000,109,2000000,21101,1,2,5,204,5,99];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        machine.run().unwrap();
+
+        assert_eq!(
+            machine.output,
+            vec![13, 3]
+        );
+        assert_eq!(machine.memory.read(2000005), 3);
+        assert_eq!(machine.memory.allocated_pages(), 2);
+    }
+
+    #[test]
+    fn negative_address(){
+        let program = vec![1101,1,1,-1,99];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::NegativeAddress { pc : 0, address : -1 })
+        );
+    }
+
+    #[test]
+    fn invalid_opcode_error(){
+        let program = vec![1101,1,1,5,42,0];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::InvalidOpcode { pc : 4, instruction : 42 })
+        );
+    }
+
+    #[test]
+    fn invalid_parameter_mode_error(){
+        let program = vec![1101,1,1,7,301,1,1,7,99];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::InvalidParameterMode { pc : 4, instruction : 301, mode : 3 })
+        );
+    }
+
+    #[test]
+    fn write_in_immediate_mode_error(){
+        let program = vec![11101,1,1,7,99];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::WriteInImmediateMode { pc : 0, instruction : 11101 })
+        );
+    }
+
+    #[test]
+    fn input_exhausted_error(){
+        let mut program = vec![3,0,99];
+
+        assert_eq!(
+            IntCodeMachine::run_program(&mut program, None),
+            Err(IntcodeError::InputExhausted { pc : 0 })
+        );
+    }
+
+    #[test]
+    fn step_through_states(){
+        let program = vec![1101,1,1,5,99,0];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        assert_eq!(machine.state(), &MachineState::Ready);
+        assert_eq!(machine.step().unwrap().state, MachineState::Running);
+        assert_eq!(machine.step().unwrap().state, MachineState::Halted);
+    }
+
+    #[test]
+    fn halted_machine_cannot_resume(){
+        let program = vec![99];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        machine.run().unwrap();
+
+        assert_eq!(
+            machine.run(),
+            Err(IntcodeError::IllegalTransition {
+                pc : 0,
+                from : Box::new(MachineState::Halted),
+                to : Box::new(MachineState::Running)
+            })
+        );
+        assert!(machine.send_input(1).is_err());
+    }
+
+    #[test]
+    fn faulted_machine_rejects_input(){
+        let program = vec![3,5,42];
+        let mut machine = IntCodeMachine::new(&program, None);
+        let fault = IntcodeError::InvalidOpcode { pc : 2, instruction : 42 };
+
+        assert_eq!(machine.run().unwrap(), MachineState::AwaitingInput);
+        assert_eq!(machine.send_input(1), Err(fault.clone()));
+        assert_eq!(machine.state(), &MachineState::Faulted(fault.clone()));
+
+        assert_eq!(
+            machine.send_input(2),
+            Err(IntcodeError::IllegalTransition {
+                pc : 2,
+                from : Box::new(MachineState::Faulted(fault)),
+                to : Box::new(MachineState::Running)
+            })
+        );
+    }
+
+    #[test]
+    fn push_input_does_not_execute(){
+        let program = vec![3,20,3,21,1,20,21,22,4,22,99];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        machine.push_inputs(&[40, 2]).unwrap();
+
+        assert_eq!(machine.state(), &MachineState::Ready);
+        assert_eq!(machine.run_until_halt().unwrap(), StopReason::Halted);
+        assert_eq!(machine.output, vec![42]);
+    }
+
+    #[test]
+    fn run_until_output_stops_after_each_value(){
+        let program = vec![104,1,104,2,3,20,4,20,99];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        assert_eq!(machine.run_until_output().unwrap(), StopReason::Output(1));
+        assert_eq!(machine.run_until_output().unwrap(), StopReason::Output(2));
+        assert_eq!(machine.run_until_output().unwrap(), StopReason::AwaitingInput);
+
+        machine.push_input(7).unwrap();
+
+        assert_eq!(machine.run_until_output().unwrap(), StopReason::Output(7));
+        assert_eq!(machine.run_until_output().unwrap(), StopReason::Halted);
+        assert_eq!(machine.output, vec![1, 2, 7]);
+    }
+
+    #[test]
+    fn run_until_input_and_halt(){
+        let program = vec![104,1,3,20,99];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        assert_eq!(machine.run_until_input().unwrap(), StopReason::AwaitingInput);
+        assert_eq!(machine.run_until_halt(), Err(IntcodeError::InputExhausted { pc : 2 }));
+        assert_eq!(machine.state(), &MachineState::AwaitingInput);
+
+        machine.push_input(0).unwrap();
+
+        assert_eq!(machine.run_until_halt().unwrap(), StopReason::Halted);
+        assert!(machine.push_input(0).is_err());
+    }
+
+    #[test]
+    fn cycle_budget_leaves_machine_resumable(){
+        let program = vec![1105,1,0];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        machine.set_cycle_budget(Some(10));
+
+        assert_eq!(machine.run(), Err(IntcodeError::BudgetExhausted { pc : 0, cycles : 10 }));
+        assert_eq!(machine.state(), &MachineState::Running);
+        assert_eq!(machine.run_until_halt(), Err(IntcodeError::BudgetExhausted { pc : 0, cycles : 20 }));
+
+        machine.set_total_budget(Some(25));
+
+        assert_eq!(machine.run(), Err(IntcodeError::BudgetExhausted { pc : 0, cycles : 25 }));
+        assert_eq!(machine.step(), Err(IntcodeError::BudgetExhausted { pc : 0, cycles : 25 }));
+    }
+
+    #[test]
+    fn budget_large_enough_to_finish(){
+        let program = vec![1101,1,1,5,99];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        machine.set_cycle_budget(Some(2));
+
+        assert_eq!(machine.run().unwrap(), MachineState::Halted);
+        assert_eq!(machine.cycles(), 2);
+    }
+
+    #[test]
+    fn detects_infinite_loop(){
+        let program = vec![1101,0,0,20,1106,0,4];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        machine.set_loop_detection(true);
+
+        assert_eq!(machine.run(), Err(IntcodeError::InfiniteLoop { pc : 4, cycles : 2 }));
+        assert_eq!(machine.state(), &MachineState::Faulted(IntcodeError::InfiniteLoop { pc : 4, cycles : 2 }));
+    }
+
+    #[test]
+    fn loop_detection_ignores_progress_and_io(){
+        // Counts down from the input, echoing each value.
+        let program = vec![3,20,4,20,1001,20,-1,20,1005,20,2,3,21,104,7,1105,1,11];
+        let mut machine = IntCodeMachine::new(&program, Some(&vec![3]));
+
+        machine.set_loop_detection(true);
+
+        assert_eq!(machine.run().unwrap(), MachineState::AwaitingInput);
+        assert_eq!(machine.send_input(0).unwrap(), MachineState::AwaitingInput);
+        assert_eq!(machine.send_input(0).unwrap(), MachineState::AwaitingInput);
+        assert_eq!(machine.output, vec![3, 2, 1, 7, 7]);
+    }
+}
@@ -0,0 +1,313 @@
+use std::collections::VecDeque;
+use std::fmt;
+use crate::int_code_computer::{IntCodeMachine, IntcodeError, MachineState};
+use crate::int_code_computer::circuit::NodeError;
+use crate::int_code_computer::word::Word;
+
+/// One `(destination, x, y)` triple, as sent in round `round`. Packets the NAT sends to
+/// wake the network come from the NAT's address.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Packet<W: Word = i64> {
+    pub round: u64,
+    pub from: usize,
+    /// The destination exactly as the machine wrote it; it need not be a valid address.
+    pub to: W,
+    pub x: W,
+    pub y: W
+}
+
+impl<W: Word> fmt::Display for Packet<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "round {}: {} -> {}: ({}, {})", self.round, self.from, self.to, self.x, self.y)
+    }
+}
+
+/// What one round of the network did.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Round<W: Word = i64> {
+    /// Packets machines sent this round.
+    pub sent: usize,
+    /// Every machine found its queue empty and nobody sent anything.
+    pub idle: bool,
+    /// The packet the NAT sent to address 0 because the round was idle.
+    pub wake: Option<Packet<W>>
+}
+
+/// The NAT was given the address of one of the network's machines, which would take
+/// that machine's packets.
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub struct NatAddressError {
+    pub address: usize,
+    pub size: usize
+}
+
+impl fmt::Display for NatAddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "NAT address {} belongs to one of the {} machines", self.address, self.size)
+    }
+}
+
+impl std::error::Error for NatAddressError {}
+
+struct Host<W: Word> {
+    machine: IntCodeMachine<W>,
+    queue: VecDeque<(W, W)>,
+    // Output of a packet that hasn't been completed yet.
+    partial: Vec<W>
+}
+
+/// Machines that exchange packets, each booted with its address as its first input.
+///
+/// The network runs in rounds. In each round every machine, in address order, is given
+/// the packets queued for it at the start of the round, or a single -1 if there were
+/// none, and runs until it needs more input. Packets sent during a round are delivered
+/// in the next one, so runs don't depend on anything but the programs. After a round in
+/// which every queue was empty and nothing was sent, the NAT sends the last packet
+/// addressed to it to machine 0. Packets to any other unknown address are logged and
+/// dropped.
+pub struct Network<W: Word = i64> {
+    hosts: Vec<Host<W>>,
+    nat_address: usize,
+    nat: Option<Packet<W>>,
+    log: Vec<Packet<W>>,
+    rounds: u64
+}
+
+impl<W: Word> Network<W> {
+    pub const DEFAULT_NAT_ADDRESS : usize = 255;
+
+    /// `size` copies of `program`, with addresses `0..size`. The NAT starts out at
+    /// `DEFAULT_NAT_ADDRESS`, so `size` can't be larger than that.
+    pub fn new(program: &[W], size: usize) -> Result<Self, NatAddressError> {
+        let hosts = (0..size).map(|address| {
+            let input = vec![W::from_i64(address as i64)];
+
+            Host { machine: IntCodeMachine::new(program, Some(&input)), queue: VecDeque::new(), partial: vec![] }
+        }).collect();
+
+        let mut network = Self { hosts, nat_address: 0, nat: None, log: vec![], rounds: 0 };
+
+        network.set_nat_address(Self::DEFAULT_NAT_ADDRESS)?;
+        Ok(network)
+    }
+
+    /// Move the NAT to `address`, which has to be outside `0..size`.
+    pub fn set_nat_address(&mut self, address: usize) -> Result<(), NatAddressError> {
+        if address < self.hosts.len() {
+            return Err(NatAddressError { address, size: self.hosts.len() });
+        }
+
+        self.nat_address = address;
+        Ok(())
+    }
+
+    pub fn machine(&self, address: usize) -> &IntCodeMachine<W> {
+        &self.hosts[address].machine
+    }
+
+    pub fn machine_mut(&mut self, address: usize) -> &mut IntCodeMachine<W> {
+        &mut self.hosts[address].machine
+    }
+
+    /// Every packet sent so far, the NAT's included, in the order they were sent.
+    pub fn log(&self) -> &[Packet<W>] {
+        &self.log
+    }
+
+    /// The last packet sent to the NAT.
+    pub fn nat(&self) -> Option<&Packet<W>> {
+        self.nat.as_ref()
+    }
+
+    pub fn rounds(&self) -> u64 {
+        self.rounds
+    }
+
+    fn address(&self, to: &W) -> Option<usize> {
+        to.to_i64().filter(|address| *address >= 0).map(|address| address as usize)
+    }
+
+    // Give the machine its input for the round and run it until it wants more.
+    fn turn(&mut self, address: usize) -> Result<(bool, Vec<Packet<W>>), IntcodeError> {
+        let round = self.rounds;
+        let host = &mut self.hosts[address];
+        let starved = host.queue.is_empty();
+
+        // Whatever is queued for a halted machine will never be read.
+        if host.machine.state() == &MachineState::Halted {
+            return Ok((true, vec![]));
+        }
+
+        match starved {
+            true => host.machine.push_input(W::from_i64(-1))?,
+            false => for (x, y) in host.queue.drain(..) {
+                host.machine.push_inputs(&[x, y])?;
+            }
+        }
+
+        host.machine.run()?;
+        host.partial.append(&mut host.machine.output);
+
+        let complete = host.partial.len() - host.partial.len() % 3;
+        let packets = host.partial.drain(..complete).collect::<Vec<W>>().chunks(3)
+            .map(|triple| Packet { round, from: address, to: triple[0].clone(), x: triple[1].clone(), y: triple[2].clone() })
+            .collect();
+
+        Ok((starved, packets))
+    }
+
+    fn deliver(&mut self, packet: Packet<W>) {
+        match self.address(&packet.to) {
+            Some(address) if address == self.nat_address => self.nat = Some(packet.clone()),
+            Some(address) if address < self.hosts.len() => self.hosts[address].queue.push_back((packet.x.clone(), packet.y.clone())),
+            _ => {}
+        }
+
+        self.log.push(packet);
+    }
+
+    /// Run a single round.
+    pub fn step(&mut self) -> Result<Round<W>, NodeError> {
+        let mut sent = vec![];
+        let mut idle = true;
+
+        self.rounds += 1;
+
+        for address in 0..self.hosts.len() {
+            let (starved, packets) = self.turn(address).map_err(|error| NodeError { node : address, error })?;
+
+            idle &= starved && packets.is_empty();
+            sent.extend(packets);
+        }
+
+        let count = sent.len();
+
+        for packet in sent {
+            self.deliver(packet);
+        }
+
+        let wake = match (idle, self.nat.clone()) {
+            (true, Some(last)) => {
+                let packet = Packet { round: self.rounds, from: self.nat_address, to: W::from_i64(0), x: last.x, y: last.y };
+
+                self.deliver(packet.clone());
+                Some(packet)
+            },
+            _ => None
+        };
+
+        Ok(Round { sent: count, idle, wake })
+    }
+
+    /// Run rounds until `stop` accepts a logged packet, and return that packet. Returns
+    /// None once every machine has halted, or the network is idle with nothing for the
+    /// NAT to send.
+    pub fn run_until<F: FnMut(&Packet<W>) -> bool>(&mut self, mut stop: F) -> Result<Option<Packet<W>>, NodeError> {
+        loop {
+            let start = self.log.len();
+            let round = self.step()?;
+
+            if let Some(packet) = self.log[start..].iter().find(|packet| stop(packet)) {
+                return Ok(Some(packet.clone()));
+            }
+
+            let halted = self.hosts.iter().all(|host| host.machine.state() == &MachineState::Halted);
+
+            if halted || (round.idle && round.wake.is_none()) {
+                return Ok(None);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod network_tests {
+    use crate::int_code_computer::network::*;
+    use crate::int_code_computer::assembler::assemble;
+
+    // Machine 0 sends (1, 10, 20). Every machine forwards whatever it receives to the NAT.
+    const RELAY : &str = "
+                in [address]
+                eq [address], #0, [first]
+                jf [first], @receive
+                out #1
+                out #10
+                out #20
+    receive:    in [x]
+                eq [x], #-1, [idle]
+                jt [idle], @receive
+                in [y]
+                out #255
+                out [x]
+                out [y]
+                jt @receive
+    address:    data 0
+    first:      data 0
+    idle:       data 0
+    x:          data 0
+    y:          data 0
+    ";
+
+    fn network() -> Network {
+        Network::new(&assemble(RELAY).unwrap(), 2).unwrap()
+    }
+
+    #[test]
+    fn routes_packets_in_rounds(){
+        let mut network = network();
+
+        assert_eq!(network.step(), Ok(Round { sent : 1, idle : false, wake : None }));
+        assert_eq!(network.step(), Ok(Round { sent : 1, idle : false, wake : None }));
+        assert_eq!(network.nat().map(|packet| packet.y), Some(20));
+
+        let round = network.step().unwrap();
+
+        assert!(round.idle);
+        assert_eq!(round.wake, Some(Packet { round : 3, from : 255, to : 0, x : 10, y : 20 }));
+        assert_eq!(
+            network.log().iter().map(|packet| packet.to_string()).collect::<Vec<String>>(),
+            vec!["round 1: 0 -> 1: (10, 20)", "round 2: 1 -> 255: (10, 20)", "round 3: 255 -> 0: (10, 20)"]
+        );
+    }
+
+    #[test]
+    fn stops_on_repeated_nat_value(){
+        let mut network = network();
+        let mut last_wake = None;
+        let repeated = network.run_until(|packet| {
+            if packet.from != 255 {
+                return false;
+            }
+
+            let repeat = last_wake == Some(packet.y);
+
+            last_wake = Some(packet.y);
+            repeat
+        }).unwrap();
+
+        assert_eq!(repeated.map(|packet| packet.round), Some(5));
+        assert_eq!(network.log().len(), 5);
+    }
+
+    #[test]
+    fn idle_without_nat_packet_stops(){
+        let mut network = Network::new(&assemble(RELAY).unwrap(), 1).unwrap();
+
+        network.set_nat_address(7).unwrap();
+
+        assert_eq!(network.run_until(|_| false), Ok(None));
+        assert_eq!(network.log().len(), 1);
+        assert!(network.nat().is_none());
+    }
+
+    #[test]
+    fn nat_cannot_take_a_machine_address(){
+        let program = assemble(RELAY).unwrap();
+        let mut network = network();
+
+        assert_eq!(network.set_nat_address(1), Err(NatAddressError { address : 1, size : 2 }));
+        assert!(network.set_nat_address(2).is_ok());
+        assert_eq!(Network::new(&program, 256).err(), Some(NatAddressError { address : 255, size : 256 }));
+        assert!(Network::new(&program, 255).is_ok());
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use crate::int_code_computer::{Opcode, OpcodeArg};
+use crate::int_code_computer::disassembler::{Listing, ListingEntry};
+use crate::int_code_computer::debugger::{memory_accesses, Access};
+use crate::int_code_computer::word::Word;
+
+#[derive(PartialEq, Copy, Clone, Debug, Default)]
+pub struct JumpCounts {
+    pub taken: u64,
+    pub not_taken: u64
+}
+
+/// Counters gathered while a machine runs with profiling enabled.
+///
+/// Only instructions that actually completed are counted; an `INPUT` that blocks is
+/// counted once it finally gets its value. Operand fetches count as reads of the cell
+/// they dereference, the write operand counts as a write only.
+#[derive(PartialEq, Clone, Debug, Default)]
+pub struct Profile {
+    pub executions: BTreeMap<usize, u64>,
+    pub opcodes: HashMap<Opcode, u64>,
+    pub modes: BTreeMap<i64, u64>,
+    pub reads: BTreeMap<usize, u64>,
+    pub writes: BTreeMap<usize, u64>,
+    pub jumps: BTreeMap<usize, JumpCounts>
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn record<W: Word>(&mut self, address: usize, opcode: Opcode, args: &[OpcodeArg<W>]) {
+        *self.executions.entry(address).or_insert(0) += 1;
+        *self.opcodes.entry(opcode).or_insert(0) += 1;
+
+        for arg in args.iter() {
+            *self.modes.entry(arg.parameter_mode).or_insert(0) += 1;
+        }
+
+        for (cell, access) in memory_accesses(&opcode, args) {
+            let counter = match access {
+                Access::Read => &mut self.reads,
+                Access::Write => &mut self.writes
+            };
+
+            *counter.entry(cell).or_insert(0) += 1;
+        }
+
+        let taken = match opcode {
+            Opcode::JumpIfTrue => !args[0].value.is_zero(),
+            Opcode::JumpIfFalse => args[0].value.is_zero(),
+            _ => return
+        };
+        let counts = self.jumps.entry(address).or_default();
+
+        match taken {
+            true => counts.taken += 1,
+            false => counts.not_taken += 1
+        }
+    }
+
+    /// Instructions executed in total.
+    pub fn total(&self) -> u64 {
+        self.executions.values().sum()
+    }
+
+    /// The `count` most executed addresses, hottest first.
+    pub fn hot_spots(&self, count: usize) -> Vec<(usize, u64)> {
+        let mut spots : Vec<(usize, u64)> = self.executions.iter().map(|(address, hits)| (*address, *hits)).collect();
+
+        spots.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        spots.truncate(count);
+        spots
+    }
+
+    fn sum(counts: &BTreeMap<usize, u64>, start: usize, end: usize) -> u64 {
+        counts.range(start..end).map(|(_, hits)| hits).sum()
+    }
+
+    /// `listing` annotated with how often each entry ran, what share of the run that
+    /// was, branch outcomes, and memory traffic on data, preceded by opcode and mode totals.
+    pub fn report<W: Word>(&self, listing: &Listing<W>) -> String {
+        let total = self.total();
+        let mut report = String::new();
+
+        writeln!(report, "{} instructions", total).unwrap();
+
+        let mut custom : Vec<&Opcode> = self.opcodes.keys().filter(|opcode| matches!(opcode, Opcode::Custom(_))).collect();
+
+        custom.sort_by_key(|opcode| opcode.code());
+
+        for opcode in Opcode::ALL.iter().chain(custom) {
+            if let Some(hits) = self.opcodes.get(opcode) {
+                writeln!(report, "  {:<10} {:>12}", opcode.to_string(), hits).unwrap();
+            }
+        }
+
+        for (mode, hits) in self.modes.iter() {
+            writeln!(report, "  mode {:<5} {:>12}", mode, hits).unwrap();
+        }
+
+        writeln!(report).unwrap();
+
+        for entry in listing.entries.iter() {
+            let start = entry.address();
+            let end = start + entry.len();
+            let hits = Profile::sum(&self.executions, start, end);
+            let share = match total {
+                0 => 0.0,
+                _ => hits as f64 * 100.0 / total as f64
+            };
+            let mut notes = vec![];
+
+            if let Some(jump) = self.jumps.get(&start) {
+                notes.push(format!("taken {}, not taken {}", jump.taken, jump.not_taken));
+            }
+
+            if let ListingEntry::Data { .. } = entry {
+                let reads = Profile::sum(&self.reads, start, end);
+                let writes = Profile::sum(&self.writes, start, end);
+
+                if reads + writes > 0 {
+                    notes.push(format!("reads {}, writes {}", reads, writes));
+                }
+            }
+
+            write!(report, "{:>12} {:>6.2}% {:>5}: {}", hits, share, start, entry).unwrap();
+
+            if !notes.is_empty() {
+                write!(report, "  ; {}", notes.join("; ")).unwrap();
+            }
+
+            writeln!(report).unwrap();
+        }
+
+        report
+    }
+}
+
+#[cfg(test)]
+mod profiler_tests {
+    use crate::int_code_computer::profiler::*;
+    use crate::int_code_computer::IntCodeMachine;
+    use crate::int_code_computer::fixtures::COUNTDOWN;
+
+    fn profiled() -> IntCodeMachine {
+        let mut machine = IntCodeMachine::new(&COUNTDOWN, None);
+
+        machine.set_profiling(true);
+        machine.run().unwrap();
+        machine
+    }
+
+    #[test]
+    fn counts_executions_opcodes_and_modes(){
+        let machine = profiled();
+        let profile = machine.profile().unwrap();
+
+        assert_eq!(profile.total(), 11);
+        assert_eq!(profile.executions[&4], 3);
+        assert_eq!(profile.opcodes[&Opcode::Add], 7);
+        assert_eq!(profile.opcodes[&Opcode::JumpIfTrue], 3);
+        assert_eq!(profile.modes[&1], 8);
+        assert_eq!(profile.hot_spots(2), vec![(4, 3), (8, 3)]);
+    }
+
+    #[test]
+    fn counts_memory_traffic_and_branches(){
+        let machine = profiled();
+        let profile = machine.profile().unwrap();
+
+        assert_eq!(profile.reads[&21], 9);
+        assert_eq!(profile.writes[&21], 4);
+        assert_eq!(profile.writes[&20], 3);
+        assert_eq!(profile.jumps[&12], JumpCounts { taken: 2, not_taken: 1 });
+    }
+
+    #[test]
+    fn report_annotates_listing(){
+        let machine = profiled();
+        let report = machine.profile().unwrap().report(&machine.disassemble());
+
+        assert!(report.starts_with("11 instructions\n  ADD                   7\n"));
+        assert!(report.contains("           3  27.27%    12: JUMP_TRUE [21], #4  ; taken 2, not taken 1\n"));
+        assert!(report.contains("           0   0.00%    16: DATA 0, 0, 0, 0, 6, 0  ; reads 12, writes 7\n"));
+    }
+}
@@ -0,0 +1,396 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+use crate::int_code_computer::{IntCodeMachine, IntcodeError, Opcode, OpcodeArg};
+use crate::int_code_computer::memory::Memory;
+use crate::int_code_computer::word::Word;
+
+/// A user-registered instruction. It carries its own shape, so a decoded
+/// `Opcode::Custom` can be sized, listed and checked for writes without the registry.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub struct CustomOpcode {
+    pub code: i64,
+    pub mnemonic: &'static str,
+    pub arity: usize,
+    writes: [bool; 3]
+}
+
+impl CustomOpcode {
+    pub fn is_write_operand(&self, index: usize) -> bool {
+        self.writes.get(index).cloned().unwrap_or(false)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Debug)]
+pub enum RegisterError {
+    /// The code belongs to a built-in instruction.
+    Reserved(i64),
+    /// Opcodes are the two lowest decimal digits of an instruction word.
+    OutOfRange(i64),
+    Duplicate(i64),
+    /// Instructions take at most three operands, like the built-in ones.
+    TooManyOperands(usize),
+    /// A write target index that is not one of the instruction's operands.
+    NoSuchOperand(usize)
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RegisterError::Reserved(code) => write!(f, "opcode {} is built in", code),
+            RegisterError::OutOfRange(code) => write!(f, "opcode {} is not in 0..=99", code),
+            RegisterError::Duplicate(code) => write!(f, "opcode {} is already registered", code),
+            RegisterError::TooManyOperands(arity) => write!(f, "{} operands, at most 3 are supported", arity),
+            RegisterError::NoSuchOperand(index) => write!(f, "write target {} is not an operand", index)
+        }
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+pub type Callback<W> = Rc<dyn Fn(&mut Host<'_, W>) -> Result<(), IntcodeError>>;
+
+/// Custom opcodes a machine decodes on top of the built-in instruction set.
+#[derive(Clone)]
+pub struct Registry<W: Word = i64> {
+    entries: BTreeMap<i64, (CustomOpcode, Callback<W>)>
+}
+
+impl<W: Word> Default for Registry<W> {
+    fn default() -> Self {
+        Self { entries: BTreeMap::new() }
+    }
+}
+
+impl<W: Word> Registry<W> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add an instruction with `arity` operands, of which those listed in `writes` are
+    /// write targets. `callback` runs each time it executes; see `Host` for what it may do.
+    pub fn register<F>(&mut self, code: i64, mnemonic: &'static str, arity: usize, writes: &[usize], callback: F) -> Result<CustomOpcode, RegisterError>
+        where F: Fn(&mut Host<'_, W>) -> Result<(), IntcodeError> + 'static
+    {
+        if Opcode::ALL.iter().any(|opcode| opcode.code() == code) {
+            return Err(RegisterError::Reserved(code));
+        }
+
+        if !(0..100).contains(&code) {
+            return Err(RegisterError::OutOfRange(code));
+        }
+
+        if self.entries.contains_key(&code) {
+            return Err(RegisterError::Duplicate(code));
+        }
+
+        if arity > 3 {
+            return Err(RegisterError::TooManyOperands(arity));
+        }
+
+        let mut opcode = CustomOpcode { code, mnemonic, arity, writes: [false; 3] };
+
+        for index in writes.iter() {
+            match *index < arity {
+                true => opcode.writes[*index] = true,
+                false => return Err(RegisterError::NoSuchOperand(*index))
+            }
+        }
+
+        self.entries.insert(code, (opcode, Rc::new(callback)));
+
+        Ok(opcode)
+    }
+
+    pub fn unregister(&mut self, code: i64) -> bool {
+        self.entries.remove(&code).is_some()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Registered opcodes in code order.
+    pub fn opcodes(&self) -> impl Iterator<Item = &CustomOpcode> {
+        self.entries.values().map(|(opcode, _)| opcode)
+    }
+
+    /// `Opcode::new`, falling back to the registered opcodes.
+    pub fn decode(&self, word: W) -> Result<(Opcode, i64), IntcodeError> {
+        let err = match Opcode::new(word.clone()) {
+            Err(err @ IntcodeError::InvalidOpcode { .. }) => err,
+            decoded => return decoded
+        };
+        let input = word.to_i64().ok_or_else(|| err.clone())?;
+        let parameter_mode = input / 100;
+
+        match self.entries.get(&(input - parameter_mode * 100)) {
+            Some((opcode, _)) => Ok((Opcode::Custom(*opcode), parameter_mode)),
+            None => Err(err)
+        }
+    }
+
+    pub(crate) fn callback(&self, code: i64) -> Option<Callback<W>> {
+        self.entries.get(&code).map(|(_, callback)| callback.clone())
+    }
+}
+
+/// What a custom instruction did besides writing its operands, so it can be undone and
+/// the machine knows where to go next.
+#[derive(PartialEq, Clone, Debug)]
+pub(crate) struct HostEffects<W: Word> {
+    pub inputs: Vec<W>,
+    /// Outputs appended to the machine's output buffer.
+    pub outputs: usize,
+    /// Every cell written, with its value before and after, in order.
+    pub writes: Vec<(usize, W, W)>,
+    pub io: bool,
+    pub jump: Option<usize>,
+    pub halted: bool,
+    pub blocked: bool
+}
+
+/// A custom instruction's view of the machine while its callback runs.
+///
+/// Operands come resolved like the built-in ones'. Memory only changes through the
+/// declared write operands, so watchpoints, profiling, code monitoring and reverse
+/// execution keep working. Unless the callback jumps, halts or blocks, the program
+/// counter moves past the instruction afterwards.
+pub struct Host<'a, W: Word> {
+    machine: &'a mut IntCodeMachine<W>,
+    opcode: CustomOpcode,
+    args: &'a [OpcodeArg<W>],
+    effects: HostEffects<W>
+}
+
+impl<'a, W: Word> Host<'a, W> {
+    pub(crate) fn new(machine: &'a mut IntCodeMachine<W>, opcode: CustomOpcode, args: &'a [OpcodeArg<W>]) -> Self {
+        let effects = HostEffects { inputs: vec![], outputs: 0, writes: vec![], io: false, jump: None, halted: false, blocked: false };
+
+        Self { machine, opcode, args, effects }
+    }
+
+    pub(crate) fn into_effects(self) -> HostEffects<W> {
+        self.effects
+    }
+
+    pub fn opcode(&self) -> CustomOpcode {
+        self.opcode
+    }
+
+    pub fn args(&self) -> &[OpcodeArg<W>] {
+        self.args
+    }
+
+    /// The resolved value of operand `index`.
+    pub fn value(&self, index: usize) -> W {
+        self.args[index].value()
+    }
+
+    pub fn memory(&self) -> &Memory<W> {
+        &self.machine.memory
+    }
+
+    pub fn program_counter(&self) -> usize {
+        self.machine.program_counter
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.machine.relative_base_offset
+    }
+
+    /// Store `value` through operand `index`, which must be a declared write target.
+    pub fn write(&mut self, index: usize, value: W) -> Result<(), IntcodeError> {
+        if !self.opcode.is_write_operand(index) {
+            return Err(IntcodeError::UndeclaredWrite { pc: self.machine.program_counter, opcode: self.opcode.code, operand: index });
+        }
+
+        let address = self.machine.write_address(&self.args[index])?;
+        let previous = self.machine.memory.read(address);
+
+        self.machine.memory.write(address, value.clone());
+        self.effects.writes.push((address, previous, value));
+
+        Ok(())
+    }
+
+    /// The next input value. When there is none the instruction blocks and is executed
+    /// again from the start once input arrives, so read input before doing anything else.
+    pub fn input(&mut self) -> Result<Option<W>, IntcodeError> {
+        if self.effects.blocked {
+            return Ok(None);
+        }
+
+        self.effects.io = true;
+
+        let input = self.machine.get_input()?;
+
+        match input.clone() {
+            Some(value) => self.effects.inputs.push(value),
+            None => {
+                // Hand back what was read so far; the retry reads it again.
+                for value in self.effects.inputs.drain(..).rev() {
+                    self.machine.input.push_front(value);
+                }
+
+                self.effects.blocked = true;
+            }
+        }
+
+        Ok(input)
+    }
+
+    pub fn output(&mut self, value: W) -> Result<(), IntcodeError> {
+        self.effects.io = true;
+
+        if self.machine.output_sink.is_none() {
+            self.effects.outputs += 1;
+        }
+
+        self.machine.put_output(value)
+    }
+
+    pub fn jump(&mut self, address: usize) {
+        self.effects.jump = Some(address);
+    }
+
+    pub fn halt(&mut self) {
+        self.effects.halted = true;
+    }
+}
+
+#[cfg(test)]
+mod registry_tests {
+    use crate::int_code_computer::registry::*;
+    use crate::int_code_computer::MachineState;
+    use crate::int_code_computer::engine::Engine;
+    use crate::int_code_computer::snapshot::Snapshot;
+
+    // `SQUARE a, [b]` stores a * a in b; `PRINT a` outputs a twice; `SUM` reads two inputs
+    // and outputs their sum.
+    fn registry() -> Registry {
+        let mut registry = Registry::new();
+
+        registry.register(20, "SQUARE", 2, &[1], |host| {
+            let value = host.value(0);
+
+            host.write(1, value * value)
+        }).unwrap();
+        registry.register(21, "PRINT", 1, &[], |host| {
+            host.output(host.value(0))?;
+            host.output(host.value(0))
+        }).unwrap();
+        registry.register(22, "SUM", 0, &[], |host| {
+            let (a, b) = match (host.input()?, host.input()?) {
+                (Some(a), Some(b)) => (a, b),
+                _ => return Ok(())
+            };
+
+            host.output(a + b)
+        }).unwrap();
+
+        registry
+    }
+
+    #[test]
+    fn runs_custom_instructions(){
+        let program = vec![120,7,9, 21,9, 22, 99, 0,0,0];
+        let mut machine = IntCodeMachine::new(&program, Some(&vec![2]));
+
+        *machine.registry_mut() = registry();
+
+        assert_eq!(machine.run(), Ok(MachineState::AwaitingInput));
+        assert_eq!(machine.output, vec![49, 49]);
+        assert_eq!(machine.program_counter(), 5);
+
+        assert_eq!(machine.send_input(3), Ok(MachineState::Halted));
+        assert_eq!(machine.output, vec![49, 49, 5]);
+    }
+
+    #[test]
+    fn listed_and_decoded_with_modes(){
+        let program = vec![120,7,9, 221,-1, 22, 99];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        assert_eq!(machine.disassemble().to_source(), "DATA 120, 7, 9, 221, -1, 22, 99\n");
+
+        *machine.registry_mut() = registry();
+
+        assert_eq!(machine.disassemble().to_source(), "SQUARE #7, [9]\nPRINT [rb-1]\nSUM\nHALT\n");
+        assert!(matches!(machine.registry().decode(20), Ok((Opcode::Custom(opcode), 0)) if opcode.arity == 2));
+        assert_eq!(machine.registry().decode(23), Err(IntcodeError::InvalidOpcode { pc : 0, instruction : 23 }));
+    }
+
+    #[test]
+    fn observed_like_built_ins(){
+        let program = vec![120,7,9, 21,9, 22, 99, 0,0,0];
+        let mut machine = IntCodeMachine::new(&program, Some(&vec![2, 3]));
+
+        *machine.registry_mut() = registry();
+        machine.set_engine(Engine::Predecoded);
+        machine.set_recording(true);
+        machine.set_profiling(true);
+
+        assert_eq!(machine.run(), Ok(MachineState::Halted));
+        assert_eq!(machine.profile().unwrap().writes.get(&9), Some(&1));
+        assert_eq!(machine.profile().unwrap().reads.get(&9), Some(&1));
+
+        assert!(machine.rewind_to(0));
+        assert_eq!(machine.memory.read(9), 0);
+        assert!(machine.output.is_empty());
+        assert_eq!(machine.run(), Ok(MachineState::Halted));
+        assert_eq!(machine.output, vec![49, 49, 5]);
+    }
+
+    #[test]
+    fn faults_on_undeclared_writes(){
+        let mut machine = IntCodeMachine::new(&[104,1, 30,5, 99, 0], None);
+        let fault = IntcodeError::UndeclaredWrite { pc : 2, opcode : 30, operand : 0 };
+
+        machine.registry_mut().register(30, "POKE", 1, &[], |host| host.write(0, 7)).unwrap();
+
+        assert_eq!(machine.run(), Err(fault.clone()));
+        assert_eq!(machine.state(), &MachineState::Faulted(fault));
+        assert_eq!(machine.output, vec![1]);
+        assert_eq!(machine.memory.read(5), 0);
+
+        let mut saved = vec![];
+
+        machine.snapshot().save(&mut saved).unwrap();
+        assert_eq!(Snapshot::load(saved.as_slice()).unwrap(), machine.snapshot());
+    }
+
+    #[test]
+    fn undo_only_restores_cells_written(){
+        // `MAYBE` declares its operand as a write target but never writes it; here the
+        // operands are immediates naming cell 3 and a negative address.
+        let program = vec![131,3, 131,-4, 99, 7];
+        let mut machine = IntCodeMachine::new(&program, None);
+
+        machine.registry_mut().register(31, "MAYBE", 1, &[0], |_| Ok(())).unwrap();
+        machine.set_recording(true);
+        machine.set_code_monitoring(true);
+
+        assert_eq!(machine.run(), Ok(MachineState::Halted));
+        assert!(machine.code_monitor().unwrap().events().is_empty());
+
+        assert!(machine.step_back());
+        assert!(machine.step_back());
+        assert!(machine.step_back());
+        assert_eq!(machine.memory, Memory::new(&program));
+    }
+
+    #[test]
+    fn rejects_bad_registrations(){
+        let mut registry : Registry = registry();
+        let noop = |_: &mut Host<i64>| Ok(());
+
+        assert_eq!(registry.register(9, "RBO2", 1, &[], noop), Err(RegisterError::Reserved(9)));
+        assert_eq!(registry.register(100, "BIG", 1, &[], noop), Err(RegisterError::OutOfRange(100)));
+        assert_eq!(registry.register(20, "AGAIN", 1, &[], noop), Err(RegisterError::Duplicate(20)));
+        assert_eq!(registry.register(30, "WIDE", 4, &[], noop), Err(RegisterError::TooManyOperands(4)));
+        assert_eq!(registry.register(30, "WRITE", 1, &[1], noop), Err(RegisterError::NoSuchOperand(1)));
+        assert!(registry.unregister(20));
+        assert_eq!(registry.opcodes().map(|opcode| opcode.mnemonic).collect::<Vec<_>>(), vec!["PRINT", "SUM"]);
+    }
+}
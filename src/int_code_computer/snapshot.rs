@@ -0,0 +1,316 @@
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use crate::int_code_computer::memory::{Memory, PAGE_SIZE};
+use crate::int_code_computer::{IntcodeError, MachineState};
+use crate::int_code_computer::word::Word;
+
+const SNAPSHOT_MAGIC: &str = "INTCODE-SNAPSHOT";
+const SNAPSHOT_VERSION: u32 = 1;
+
+/// Everything needed to resume a machine exactly where it was: memory, registers,
+/// queued input, buffered output and execution state. Attached input sources and output
+/// sinks are not part of a snapshot.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot<W: Word = i64> {
+    pub memory: Memory<W>,
+    pub program_counter: usize,
+    pub relative_base_offset: i64,
+    pub input: VecDeque<W>,
+    pub output: Vec<W>,
+    pub state: MachineState
+}
+
+impl<W: Word> Snapshot<W> {
+    /// Write the snapshot in the versioned, line-based text format:
+    ///
+    /// ```text
+    /// INTCODE-SNAPSHOT 1
+    /// pc 12
+    /// rb 0
+    /// state awaiting_input
+    /// input 1,2
+    /// output
+    /// image 3,0,99
+    /// page 4 0,0,7,...
+    /// ```
+    pub fn save<T: Write>(&self, writer: &mut T) -> io::Result<()> {
+        writeln!(writer, "{} {}", SNAPSHOT_MAGIC, SNAPSHOT_VERSION)?;
+        writeln!(writer, "pc {}", self.program_counter)?;
+        writeln!(writer, "rb {}", self.relative_base_offset)?;
+        writeln!(writer, "state {}", encode_state(&self.state)?)?;
+        writeln!(writer, "input {}", join(self.input.iter()))?;
+        writeln!(writer, "output {}", join(self.output.iter()))?;
+        writeln!(writer, "image {}", join(self.memory.image().iter()))?;
+
+        for (page, cells) in self.memory.pages() {
+            writeln!(writer, "page {} {}", page, join(cells.iter()))?;
+        }
+
+        writer.flush()
+    }
+
+    pub fn load<R: BufRead>(reader: R) -> io::Result<Self> {
+        let mut lines = reader.lines().enumerate();
+        let header = match lines.next() {
+            Some((_, line)) => line?,
+            None => return Err(invalid(1, "empty snapshot"))
+        };
+
+        match header.trim().split_once(' ') {
+            Some((SNAPSHOT_MAGIC, version)) if version == SNAPSHOT_VERSION.to_string() => {},
+            Some((SNAPSHOT_MAGIC, version)) => return Err(invalid(1, &format!("unsupported snapshot version {}", version))),
+            _ => return Err(invalid(1, "not an intcode snapshot"))
+        }
+
+        let mut program_counter = None;
+        let mut relative_base_offset = None;
+        let mut state = None;
+        let mut input = None;
+        let mut output = None;
+        let mut memory : Option<Memory<W>> = None;
+
+        for (index, line) in lines {
+            let line = line?;
+            let number = index + 1;
+            let (key, value) = line.trim().split_once(' ').unwrap_or((line.trim(), ""));
+
+            match key {
+                "" => {},
+                "pc" => program_counter = Some(parse::<usize>(number, value)?),
+                "rb" => relative_base_offset = Some(parse::<i64>(number, value)?),
+                "state" => state = Some(decode_state(number, value)?),
+                "input" => input = Some(parse_list(number, value)?.into_iter().collect()),
+                "output" => output = Some(parse_list(number, value)?),
+                "image" => memory = Some(Memory::new(&parse_list(number, value)?)),
+                "page" => {
+                    let memory = memory.as_mut().ok_or_else(|| invalid(number, "page before image"))?;
+                    let (page, cells) = value.split_once(' ').unwrap_or((value, ""));
+                    let cells = parse_list(number, cells)?;
+
+                    if cells.len() != PAGE_SIZE {
+                        return Err(invalid(number, &format!("page has {} cells, expected {}", cells.len(), PAGE_SIZE)));
+                    }
+
+                    memory.load_page(parse::<usize>(number, page)?, cells);
+                },
+                _ => return Err(invalid(number, &format!("unknown field {:?}", key)))
+            }
+        }
+
+        let missing = |field: &str| invalid(0, &format!("missing {}", field));
+
+        Ok(Snapshot {
+            memory: memory.ok_or_else(|| missing("image"))?,
+            program_counter: program_counter.ok_or_else(|| missing("pc"))?,
+            relative_base_offset: relative_base_offset.ok_or_else(|| missing("rb"))?,
+            input: input.ok_or_else(|| missing("input"))?,
+            output: output.ok_or_else(|| missing("output"))?,
+            state: state.ok_or_else(|| missing("state"))?
+        })
+    }
+
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.save(&mut BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::load(BufReader::new(File::open(path)?))
+    }
+}
+
+fn join<'a, W: Word, I: Iterator<Item = &'a W>>(values: I) -> String {
+    values.map(|value| value.to_string()).collect::<Vec<String>>().join(",")
+}
+
+fn invalid(line: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("snapshot line {}: {}", line, message))
+}
+
+fn parse<T: std::str::FromStr>(line: usize, text: &str) -> io::Result<T> {
+    text.trim().parse::<T>().map_err(|_| invalid(line, &format!("invalid number {:?}", text)))
+}
+
+fn parse_list<W: Word>(line: usize, text: &str) -> io::Result<Vec<W>> {
+    match text.trim() {
+        "" => Ok(vec![]),
+        text => text.split(',').map(|value| parse::<W>(line, value)).collect()
+    }
+}
+
+// Only faults raised while executing can end up in a snapshot, so those are the only
+// errors the format needs to carry.
+fn encode_state(state: &MachineState) -> io::Result<String> {
+    let encoded = match state {
+        MachineState::Ready => String::from("ready"),
+        MachineState::Running => String::from("running"),
+        MachineState::AwaitingInput => String::from("awaiting_input"),
+        MachineState::Halted => String::from("halted"),
+        MachineState::Faulted(err) => match err {
+            IntcodeError::InvalidOpcode { pc, instruction } =>
+                format!("faulted invalid_opcode {} {}", pc, instruction),
+            IntcodeError::InvalidParameterMode { pc, instruction, mode } =>
+                format!("faulted invalid_parameter_mode {} {} {}", pc, instruction, mode),
+            IntcodeError::NegativeAddress { pc, address } =>
+                format!("faulted negative_address {} {}", pc, address),
+            IntcodeError::WriteInImmediateMode { pc, instruction } =>
+                format!("faulted write_in_immediate_mode {} {}", pc, instruction),
+            IntcodeError::InputExhausted { pc } =>
+                format!("faulted input_exhausted {}", pc),
+            IntcodeError::IoFailure { pc, message } =>
+                format!("faulted io_failure {} {}", pc, message.replace('\n', " ")),
+            IntcodeError::InfiniteLoop { pc, cycles } =>
+                format!("faulted infinite_loop {} {}", pc, cycles),
+            IntcodeError::ArithmeticOverflow { pc, instruction } =>
+                format!("faulted arithmetic_overflow {} {}", pc, instruction),
+            IntcodeError::AddressOutOfRange { pc, address } =>
+                format!("faulted address_out_of_range {} {}", pc, address),
+            IntcodeError::UndeclaredWrite { pc, opcode, operand } =>
+                format!("faulted undeclared_write {} {} {}", pc, opcode, operand),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("cannot snapshot fault {}", err)))
+        }
+    };
+
+    Ok(encoded)
+}
+
+fn decode_state(line: usize, text: &str) -> io::Result<MachineState> {
+    let mut fields = text.trim().splitn(4, ' ');
+    let kind = fields.next().unwrap_or("");
+
+    let state = match kind {
+        "ready" => MachineState::Ready,
+        "running" => MachineState::Running,
+        "awaiting_input" => MachineState::AwaitingInput,
+        "halted" => MachineState::Halted,
+        "faulted" => {
+            let fault = fields.next().unwrap_or("");
+            let pc = parse::<usize>(line, fields.next().unwrap_or(""))?;
+            let rest = fields.next().unwrap_or("");
+            let numbers = || -> io::Result<Vec<i64>> { rest.split(' ').map(|value| parse::<i64>(line, value)).collect() };
+
+            let err = match (fault, numbers()) {
+                ("invalid_opcode", Ok(ref values)) if values.len() == 1 =>
+                    IntcodeError::InvalidOpcode { pc, instruction: values[0] },
+                ("invalid_parameter_mode", Ok(ref values)) if values.len() == 2 =>
+                    IntcodeError::InvalidParameterMode { pc, instruction: values[0], mode: values[1] },
+                ("negative_address", Ok(ref values)) if values.len() == 1 =>
+                    IntcodeError::NegativeAddress { pc, address: values[0] },
+                ("write_in_immediate_mode", Ok(ref values)) if values.len() == 1 =>
+                    IntcodeError::WriteInImmediateMode { pc, instruction: values[0] },
+                ("input_exhausted", _) if rest.is_empty() =>
+                    IntcodeError::InputExhausted { pc },
+                ("infinite_loop", Ok(ref values)) if values.len() == 1 && values[0] >= 0 =>
+                    IntcodeError::InfiniteLoop { pc, cycles: values[0] as u64 },
+                ("arithmetic_overflow", Ok(ref values)) if values.len() == 1 =>
+                    IntcodeError::ArithmeticOverflow { pc, instruction: values[0] },
+                ("address_out_of_range", _) if !rest.is_empty() && !rest.contains(' ') =>
+                    IntcodeError::AddressOutOfRange { pc, address: rest.to_string() },
+                ("undeclared_write", Ok(ref values)) if values.len() == 2 && values[1] >= 0 =>
+                    IntcodeError::UndeclaredWrite { pc, opcode: values[0], operand: values[1] as usize },
+                ("io_failure", _) =>
+                    IntcodeError::IoFailure { pc, message: rest.to_string() },
+                _ => return Err(invalid(line, &format!("invalid fault {:?}", text)))
+            };
+
+            MachineState::Faulted(err)
+        },
+        _ => return Err(invalid(line, &format!("unknown state {:?}", kind)))
+    };
+
+    Ok(state)
+}
+
+#[cfg(test)]
+mod snapshot_tests {
+    use crate::int_code_computer::snapshot::*;
+    use crate::int_code_computer::{IntCodeMachine, StopReason};
+
+    // Reads a number and outputs a running total, forever.
+    fn accumulator() -> Vec<i64> {
+        vec![3,20,1,20,21,21,4,21,1105,1,0]
+    }
+
+    #[test]
+    fn restore_resumes_from_snapshot(){
+        let mut machine = IntCodeMachine::new(&accumulator(), Some(&vec![1, 2]));
+
+        machine.run().unwrap();
+        let snapshot = machine.snapshot();
+
+        machine.push_input(10).unwrap();
+        machine.run().unwrap();
+        assert_eq!(machine.output, vec![1, 3, 13]);
+
+        machine.restore(&snapshot);
+        assert_eq!(machine.output, vec![1, 3]);
+
+        machine.push_input(20).unwrap();
+        machine.run().unwrap();
+        assert_eq!(machine.output, vec![1, 3, 23]);
+    }
+
+    #[test]
+    fn machines_cloned_from_a_snapshot_are_independent(){
+        let mut machine = IntCodeMachine::new(&accumulator(), Some(&vec![5]));
+
+        machine.run().unwrap();
+
+        let snapshot = machine.snapshot();
+        let mut left = IntCodeMachine::from_snapshot(&snapshot);
+        let mut right = IntCodeMachine::from_snapshot(&snapshot);
+
+        left.push_input(1).unwrap();
+        right.push_input(100).unwrap();
+
+        assert_eq!(left.run_until_output().unwrap(), StopReason::Output(6));
+        assert_eq!(right.run_until_output().unwrap(), StopReason::Output(105));
+        assert_eq!(machine.snapshot(), snapshot);
+    }
+
+    #[test]
+    fn save_and_load_round_trip(){
+        let program = vec![3,5000,3,5001,1,5000,5001,3000,99];
+        let mut machine = IntCodeMachine::new(&program, Some(&vec![4]));
+
+        machine.run().unwrap();
+        machine.push_input(9).unwrap();
+        machine.push_input(7).unwrap();
+
+        let snapshot = machine.snapshot();
+        let mut saved = vec![];
+
+        snapshot.save(&mut saved).unwrap();
+
+        let text = String::from_utf8(saved.clone()).unwrap();
+        assert!(text.starts_with("INTCODE-SNAPSHOT 1\npc 2\nrb 0\nstate awaiting_input\ninput 9,7\noutput \nimage 3,5000,3"));
+
+        let loaded = Snapshot::load(saved.as_slice()).unwrap();
+        assert_eq!(loaded, snapshot);
+
+        let mut resumed = IntCodeMachine::from_snapshot(&loaded);
+        resumed.run().unwrap();
+        assert_eq!(resumed.memory.read(3000), 13);
+    }
+
+    #[test]
+    fn faulted_state_round_trips(){
+        let mut machine = IntCodeMachine::new(&[1101,1,1,5,42], None);
+
+        assert!(machine.run().is_err());
+
+        let mut saved = vec![];
+        machine.snapshot().save(&mut saved).unwrap();
+
+        assert_eq!(Snapshot::load(saved.as_slice()).unwrap(), machine.snapshot());
+    }
+
+    #[test]
+    fn rejects_other_versions(){
+        let err = Snapshot::<i64>::load("INTCODE-SNAPSHOT 2\n".as_bytes()).unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "snapshot line 1: unsupported snapshot version 2");
+    }
+}
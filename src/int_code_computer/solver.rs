@@ -0,0 +1,497 @@
+use std::collections::BTreeMap;
+use std::ops::RangeInclusive;
+use crate::int_code_computer::{IntCodeMachine, MachineState};
+use crate::int_code_computer::snapshot::Snapshot;
+use crate::int_code_computer::symbolic::{Expr, Path, PathEnd, SymbolicExecutor};
+
+/// Something the solver picks a value for.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash, Copy, Clone, Debug)]
+pub enum Variable {
+    /// The initial contents of a memory cell, patched in before the run.
+    Cell(usize),
+    /// The nth value the program reads, counting from 0.
+    Input(usize)
+}
+
+impl Variable {
+    fn symbol(&self) -> Expr {
+        match *self {
+            Variable::Cell(address) => Expr::Cell(address),
+            Variable::Input(index) => Expr::Input(index)
+        }
+    }
+}
+
+/// What the halted machine has to look like.
+pub enum Target {
+    Cell { address: usize, value: i64 },
+    /// The `index`th output is `value`.
+    Output { index: usize, value: i64 },
+    /// Anything else about memory or outputs. Only ever solved by search.
+    Predicate(Box<dyn Fn(&Snapshot) -> bool>)
+}
+
+pub type Assignment = BTreeMap<Variable, i64>;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum Method {
+    /// Inverted from the expressions the symbolic executor built.
+    Symbolic,
+    /// Found by running the machine.
+    Search
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub struct Solution {
+    /// A value for every variable. Those the result doesn't depend on are at the start
+    /// of their range.
+    pub assignment: Assignment,
+    pub method: Method
+}
+
+enum Inversion {
+    Value(i64),
+    Impossible,
+    // The expression is too complicated to invert; try every value instead.
+    Unknown
+}
+
+/// Finds values for patchable cells and input slots that make a program reach a target.
+///
+/// The solver first runs the program symbolically and, on each path that halts, solves
+/// the target's expression for one variable after fixing the others. If the symbolic
+/// executor can't follow the program everywhere, or the target is a predicate, it falls
+/// back to running the machine for each assignment. That search forks machines from a
+/// snapshot at each input it needs, so runs share their common prefix, a program that
+/// halts early never has its later inputs enumerated, and a wrong output cuts off
+/// everything after it.
+///
+/// Input variables are fed in slot order; a program that reads a slot without one
+/// never reaches the target.
+pub struct Solver {
+    program: Vec<i64>,
+    variables: Vec<(Variable, RangeInclusive<i64>)>,
+    target: Target,
+    cycle_budget: u64
+}
+
+impl Solver {
+    pub const DEFAULT_CYCLE_BUDGET : u64 = 100_000;
+
+    pub fn new(program: &[i64], target: Target) -> Self {
+        Self { program: program.to_vec(), variables: vec![], target, cycle_budget: Self::DEFAULT_CYCLE_BUDGET }
+    }
+
+    pub fn add_variable(&mut self, variable: Variable, range: RangeInclusive<i64>) {
+        self.variables.retain(|(existing, _)| *existing != variable);
+        self.variables.push((variable, range));
+    }
+
+    /// Instructions a single run may execute before it's given up on.
+    pub fn set_cycle_budget(&mut self, budget: u64) {
+        self.cycle_budget = budget;
+    }
+
+    fn range(&self, variable: Variable) -> Option<&RangeInclusive<i64>> {
+        self.variables.iter().find(|(existing, _)| *existing == variable).map(|(_, range)| range)
+    }
+
+    pub fn solve(&self) -> Option<Solution> {
+        if let Target::Predicate(_) = self.target {
+            return self.search().map(|assignment| Solution { assignment, method: Method::Search });
+        }
+
+        let (found, complete) = self.solve_symbolically();
+
+        match found {
+            Some(assignment) => Some(Solution { assignment, method: Method::Symbolic }),
+            None if complete => None,
+            None => self.search().map(|assignment| Solution { assignment, method: Method::Search })
+        }
+    }
+
+    fn machine(&self, assignment: &Assignment) -> IntCodeMachine {
+        let mut machine = IntCodeMachine::new(&self.program, None);
+
+        for (variable, value) in assignment.iter() {
+            if let Variable::Cell(address) = *variable {
+                machine.memory.write(address, *value);
+            }
+        }
+
+        machine.set_cycle_budget(Some(self.cycle_budget));
+        machine
+    }
+
+    fn reached(&self, snapshot: &Snapshot) -> bool {
+        match &self.target {
+            Target::Cell { address, value } => snapshot.memory.read(*address) == *value,
+            Target::Output { index, value } => snapshot.output.get(*index) == Some(value),
+            Target::Predicate(predicate) => predicate(snapshot)
+        }
+    }
+
+    // Give every variable the assignment doesn't mention the start of its range.
+    fn complete(&self, assignment: &Assignment) -> Assignment {
+        let mut complete = assignment.clone();
+
+        for (variable, range) in self.variables.iter() {
+            complete.entry(*variable).or_insert(*range.start());
+        }
+
+        complete
+    }
+
+    // Run the program with the assignment and see whether it gets there.
+    fn check(&self, assignment: &Assignment) -> bool {
+        let mut machine = self.machine(assignment);
+        let inputs = (0..).map_while(|slot| assignment.get(&Variable::Input(slot)).cloned()).collect::<Vec<i64>>();
+
+        machine.push_inputs(&inputs).is_ok()
+            && machine.run() == Ok(MachineState::Halted)
+            && self.reached(&machine.snapshot())
+    }
+
+    // Returns a verified assignment if one was found, and whether every path through the
+    // program was accounted for, i.e. whether not finding one means there is none.
+    fn solve_symbolically(&self) -> (Option<Assignment>, bool) {
+        let mut executor = SymbolicExecutor::new(&self.program);
+
+        executor.set_step_limit(self.cycle_budget);
+
+        for (variable, _) in self.variables.iter() {
+            if let Variable::Cell(address) = *variable {
+                executor.make_symbolic(address);
+            }
+        }
+
+        let mut complete = true;
+
+        for path in executor.explore() {
+            match path.end {
+                PathEnd::Halted => {},
+                PathEnd::Fault(_) => continue,
+                _ => {
+                    complete = false;
+                    continue;
+                }
+            }
+
+            let goal = match self.target {
+                Target::Cell { address, .. } => path.cell(address),
+                Target::Output { index, .. } => match path.outputs.get(index) {
+                    Some(output) => output.clone(),
+                    None => continue
+                },
+                Target::Predicate(_) => unreachable!()
+            };
+
+            if let Some(assignment) = self.solve_path(&path, &goal) {
+                return (Some(assignment), true);
+            }
+        }
+
+        (None, complete)
+    }
+
+    fn solve_path(&self, path: &Path, goal: &Expr) -> Option<Assignment> {
+        let value = match self.target {
+            Target::Cell { value, .. }|Target::Output { value, .. } => value,
+            Target::Predicate(_) => return None
+        };
+
+        // Only inputs with a variable can be supplied.
+        if (0..path.inputs).any(|slot| self.range(Variable::Input(slot)).is_none()) {
+            return None;
+        }
+
+        let mut symbols = vec![goal.symbols()];
+
+        symbols.extend(path.constraints.iter().map(|constraint| constraint.expr.symbols()));
+
+        let mut free = self.variables.iter()
+            .filter(|(variable, _)| symbols.iter().any(|(inputs, cells)| match *variable {
+                Variable::Cell(address) => cells.contains(&address),
+                Variable::Input(index) => inputs.contains(&index)
+            }))
+            .cloned()
+            .collect::<Vec<(Variable, RangeInclusive<i64>)>>();
+
+        // Leave for last the widest variable that appears only once, so it can be
+        // inverted rather than enumerated.
+        let last = free.iter().enumerate()
+            .filter(|(_, (variable, _))| occurrences(goal, &variable.symbol()) == 1)
+            .max_by_key(|(_, (_, range))| range.end().saturating_sub(*range.start()))
+            .map(|(index, _)| index);
+        let last = last.map(|index| free.remove(index));
+        let mut assignment = Assignment::new();
+
+        self.enumerate(path, goal, value, &free, last.as_ref(), &mut assignment)
+    }
+
+    fn enumerate(
+        &self,
+        path: &Path,
+        goal: &Expr,
+        value: i64,
+        free: &[(Variable, RangeInclusive<i64>)],
+        last: Option<&(Variable, RangeInclusive<i64>)>,
+        assignment: &mut Assignment
+    ) -> Option<Assignment> {
+        let (inputs, cells) = split(assignment);
+
+        // Prune as soon as a branch decision is known to go the other way.
+        if path.constraints.iter().any(|constraint| {
+            constraint.expr.substitute(&inputs, &cells).as_const().is_some_and(|result| (result != 0) != constraint.nonzero)
+        }) {
+            return None;
+        }
+
+        if let Some(((variable, range), rest)) = free.split_first() {
+            for candidate in range.clone() {
+                assignment.insert(*variable, candidate);
+
+                if let Some(found) = self.enumerate(path, goal, value, rest, last, assignment) {
+                    return Some(found);
+                }
+            }
+
+            assignment.remove(variable);
+            return None;
+        }
+
+        let (variable, range) = match last {
+            Some(last) => last,
+            None => return self.accept(path, goal, value, assignment)
+        };
+        let candidates = match invert(&goal.substitute(&inputs, &cells), &variable.symbol(), value) {
+            Inversion::Value(candidate) if range.contains(&candidate) => Some(candidate..=candidate),
+            Inversion::Value(_)|Inversion::Impossible => None,
+            Inversion::Unknown => Some(range.clone())
+        };
+
+        for candidate in candidates.into_iter().flatten() {
+            assignment.insert(*variable, candidate);
+
+            if let Some(found) = self.accept(path, goal, value, assignment) {
+                return Some(found);
+            }
+        }
+
+        assignment.remove(variable);
+        None
+    }
+
+    fn accept(&self, path: &Path, goal: &Expr, value: i64, assignment: &Assignment) -> Option<Assignment> {
+        let (inputs, cells) = split(assignment);
+        let holds = |expr: &Expr, expected: bool| expr.substitute(&inputs, &cells).as_const().map(|result| (result != 0) == expected);
+        let satisfied = goal.substitute(&inputs, &cells).as_const() == Some(value)
+            && path.constraints.iter().all(|constraint| holds(&constraint.expr, constraint.nonzero) == Some(true));
+        let assignment = self.complete(assignment);
+
+        match satisfied && self.check(&assignment) {
+            true => Some(assignment),
+            false => None
+        }
+    }
+
+    fn search(&self) -> Option<Assignment> {
+        let cells = self.variables.iter()
+            .filter(|(variable, _)| matches!(variable, Variable::Cell(_)))
+            .cloned()
+            .collect::<Vec<(Variable, RangeInclusive<i64>)>>();
+
+        self.search_cells(&cells, &mut Assignment::new())
+    }
+
+    fn search_cells(&self, cells: &[(Variable, RangeInclusive<i64>)], assignment: &mut Assignment) -> Option<Assignment> {
+        let ((variable, range), rest) = match cells.split_first() {
+            Some(next) => next,
+            None => return self.search_inputs(self.machine(assignment), 0, assignment)
+        };
+
+        for candidate in range.clone() {
+            assignment.insert(*variable, candidate);
+
+            if let Some(found) = self.search_cells(rest, assignment) {
+                return Some(found);
+            }
+        }
+
+        assignment.remove(variable);
+        None
+    }
+
+    fn search_inputs(&self, mut machine: IntCodeMachine, slot: usize, assignment: &mut Assignment) -> Option<Assignment> {
+        let state = machine.run().ok()?;
+
+        if let Target::Output { index, value } = self.target {
+            if machine.output.get(index).is_some_and(|output| *output != value) {
+                return None;
+            }
+        }
+
+        match state {
+            MachineState::Halted => match self.reached(&machine.snapshot()) {
+                true => Some(self.complete(assignment)),
+                false => None
+            },
+            MachineState::AwaitingInput => {
+                let variable = Variable::Input(slot);
+                let snapshot = machine.snapshot();
+
+                for candidate in self.range(variable)?.clone() {
+                    let mut next = IntCodeMachine::from_snapshot(&snapshot);
+
+                    next.set_cycle_budget(Some(self.cycle_budget));
+                    next.push_input(candidate).ok()?;
+                    assignment.insert(variable, candidate);
+
+                    if let Some(found) = self.search_inputs(next, slot + 1, assignment) {
+                        return Some(found);
+                    }
+                }
+
+                assignment.remove(&variable);
+                None
+            },
+            _ => None
+        }
+    }
+}
+
+fn split(assignment: &Assignment) -> (BTreeMap<usize, i64>, BTreeMap<usize, i64>) {
+    let mut inputs = BTreeMap::new();
+    let mut cells = BTreeMap::new();
+
+    for (variable, value) in assignment.iter() {
+        match *variable {
+            Variable::Input(index) => inputs.insert(index, *value),
+            Variable::Cell(address) => cells.insert(address, *value)
+        };
+    }
+
+    (inputs, cells)
+}
+
+fn occurrences(expr: &Expr, symbol: &Expr) -> usize {
+    match expr {
+        _ if expr == symbol => 1,
+        Expr::Add(lhs, rhs)|Expr::Mul(lhs, rhs)|Expr::LessThan(lhs, rhs)|Expr::Equals(lhs, rhs) =>
+            occurrences(lhs, symbol) + occurrences(rhs, symbol),
+        _ => 0
+    }
+}
+
+// Solve `expr == value` for `symbol`, which appears in it once; everything else in
+// `expr` has already been folded to constants.
+fn invert(expr: &Expr, symbol: &Expr, value: i64) -> Inversion {
+    if expr == symbol {
+        return Inversion::Value(value);
+    }
+
+    let (lhs, rhs) = match expr {
+        Expr::Add(lhs, rhs)|Expr::Mul(lhs, rhs)|Expr::LessThan(lhs, rhs)|Expr::Equals(lhs, rhs) => (lhs, rhs),
+        _ => return Inversion::Impossible
+    };
+    let (constant, inner) = match (lhs.as_const(), rhs.as_const()) {
+        (Some(constant), None) => (constant, rhs),
+        (None, Some(constant)) => (constant, lhs),
+        _ => return Inversion::Unknown
+    };
+
+    match expr {
+        Expr::Add(..) => invert(inner, symbol, value.wrapping_sub(constant)),
+        // Wrapping multiplication can have other solutions, but they're too large to be
+        // in anyone's range.
+        Expr::Mul(..) => match value.checked_rem(constant) {
+            Some(0) => invert(inner, symbol, value / constant),
+            _ => Inversion::Impossible
+        },
+        Expr::Equals(..) if value == 1 => invert(inner, symbol, constant),
+        Expr::LessThan(..)|Expr::Equals(..) if value != 0 && value != 1 => Inversion::Impossible,
+        _ => Inversion::Unknown
+    }
+}
+
+#[cfg(test)]
+mod solver_tests {
+    use crate::int_code_computer::solver::*;
+    use crate::int_code_computer::assembler::assemble;
+
+    #[test]
+    fn inverts_repeated_symbols(){
+        // [0] = [9] * [9] + [10]
+        let mut solver = Solver::new(&[2,9,9,0,1,0,10,0,99,0,0], Target::Cell { address : 0, value : 23 });
+
+        solver.add_variable(Variable::Cell(9), 0..=9);
+        solver.add_variable(Variable::Cell(10), 0..=9);
+
+        let solution = solver.solve().unwrap();
+
+        assert_eq!(solution.method, Method::Symbolic);
+        assert_eq!(solution.assignment, vec![(Variable::Cell(9), 4), (Variable::Cell(10), 7)].into_iter().collect());
+    }
+
+    #[test]
+    fn solves_each_branch(){
+        let program = assemble("
+                    in [x]
+                    lt [x], #10, [small]
+                    jf [small], @large
+                    mul [x], #3, [x]
+                    out [x]
+                    hlt
+        large:      add [x], #100, [x]
+                    out [x]
+                    hlt
+        x:          data 0
+        small:      data 0
+        ").unwrap();
+
+        for (output, input) in [(21, Some(7)), (115, Some(15)), (30, None)].iter() {
+            let mut solver = Solver::new(&program, Target::Output { index : 0, value : *output });
+
+            solver.add_variable(Variable::Input(0), 0..=50);
+
+            let solution = solver.solve();
+
+            assert_eq!(solution.as_ref().map(|solution| solution.method), input.map(|_| Method::Symbolic));
+            assert_eq!(solution.map(|solution| solution.assignment[&Variable::Input(0)]), *input);
+        }
+    }
+
+    #[test]
+    fn searches_when_addresses_are_symbolic(){
+        // [0] = [noun] + [verb], day 2's first instruction.
+        let mut solver = Solver::new(&[1,0,0,0,99,10,20,30,40], Target::Cell { address : 0, value : 70 });
+
+        solver.add_variable(Variable::Cell(1), 5..=8);
+        solver.add_variable(Variable::Cell(2), 5..=8);
+
+        let solution = solver.solve().unwrap();
+
+        assert_eq!(solution.method, Method::Search);
+        assert_eq!(solution.assignment, vec![(Variable::Cell(1), 7), (Variable::Cell(2), 8)].into_iter().collect());
+    }
+
+    #[test]
+    fn searches_for_predicates(){
+        // Echoes two inputs.
+        let mut solver = Solver::new(&[3,0,4,0,3,0,4,0,99], Target::Predicate(Box::new(|snapshot| {
+            snapshot.output.iter().sum::<i64>() == 10 && snapshot.output[0] > snapshot.output[1]
+        })));
+
+        solver.add_variable(Variable::Input(0), 0..=9);
+        solver.add_variable(Variable::Input(1), 0..=9);
+        solver.add_variable(Variable::Cell(20), 1..=3);
+
+        let solution = solver.solve().unwrap();
+
+        assert_eq!(solution.method, Method::Search);
+        assert_eq!(
+            solution.assignment,
+            vec![(Variable::Input(0), 6), (Variable::Input(1), 4), (Variable::Cell(20), 1)].into_iter().collect()
+        );
+    }
+}
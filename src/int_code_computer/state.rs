@@ -0,0 +1,32 @@
+use crate::int_code_computer::error::IntcodeError;
+use crate::int_code_computer::debugger::BreakReason;
+use crate::int_code_computer::word::Word;
+
+/// Where a machine is in its lifecycle.
+///
+/// `Ready` -> `Running` on the first `run`/`step`. A running machine either keeps
+/// running, parks in `AwaitingInput` until input arrives, or stops for good in `Halted`
+/// or `Faulted`. Nothing leaves `Halted` or `Faulted`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MachineState {
+    Ready,
+    Running,
+    AwaitingInput,
+    Halted,
+    Faulted(IntcodeError)
+}
+
+impl MachineState {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, MachineState::Halted | MachineState::Faulted(_))
+    }
+}
+
+/// Why one of the `run_until_*` drivers handed control back.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StopReason<W: Word = i64> {
+    Output(W),
+    AwaitingInput,
+    Halted,
+    Break(BreakReason<W>)
+}
@@ -0,0 +1,570 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::rc::Rc;
+use crate::int_code_computer::{IntcodeError, Opcode};
+
+/// A value computed from the symbols of a run. Arithmetic wraps, like the default
+/// `Arithmetic::Wrapping`.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub enum Expr {
+    Const(i64),
+    /// The nth value the program read, counting from 0.
+    Input(usize),
+    /// The initial contents of a cell marked symbolic.
+    Cell(usize),
+    Add(Rc<Expr>, Rc<Expr>),
+    Mul(Rc<Expr>, Rc<Expr>),
+    LessThan(Rc<Expr>, Rc<Expr>),
+    Equals(Rc<Expr>, Rc<Expr>)
+}
+
+impl Expr {
+    pub fn sum(lhs: Expr, rhs: Expr) -> Expr {
+        match (lhs, rhs) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.wrapping_add(b)),
+            (Expr::Const(0), other)|(other, Expr::Const(0)) => other,
+            // Keep counters from growing a term per iteration.
+            (Expr::Add(lhs, inner), Expr::Const(b)) if inner.as_const().is_some() =>
+                Expr::sum((*lhs).clone(), Expr::Const(inner.as_const().unwrap().wrapping_add(b))),
+            (lhs, rhs) => Expr::Add(Rc::new(lhs), Rc::new(rhs))
+        }
+    }
+
+    pub fn product(lhs: Expr, rhs: Expr) -> Expr {
+        match (lhs, rhs) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const(a.wrapping_mul(b)),
+            (Expr::Const(0), _)|(_, Expr::Const(0)) => Expr::Const(0),
+            (Expr::Const(1), other)|(other, Expr::Const(1)) => other,
+            (lhs, rhs) => Expr::Mul(Rc::new(lhs), Rc::new(rhs))
+        }
+    }
+
+    pub fn less_than(lhs: Expr, rhs: Expr) -> Expr {
+        match (lhs, rhs) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a < b) as i64),
+            (lhs, rhs) if lhs == rhs => Expr::Const(0),
+            (lhs, rhs) => Expr::LessThan(Rc::new(lhs), Rc::new(rhs))
+        }
+    }
+
+    pub fn equals(lhs: Expr, rhs: Expr) -> Expr {
+        match (lhs, rhs) {
+            (Expr::Const(a), Expr::Const(b)) => Expr::Const((a == b) as i64),
+            (lhs, rhs) if lhs == rhs => Expr::Const(1),
+            (lhs, rhs) => Expr::Equals(Rc::new(lhs), Rc::new(rhs))
+        }
+    }
+
+    pub fn as_const(&self) -> Option<i64> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    /// Evaluate with concrete values for the symbols. Returns None if the expression
+    /// uses an input past the end of `inputs` or a cell missing from `cells`.
+    pub fn eval(&self, inputs: &[i64], cells: &BTreeMap<usize, i64>) -> Option<i64> {
+        match self {
+            Expr::Const(value) => Some(*value),
+            Expr::Input(index) => inputs.get(*index).cloned(),
+            Expr::Cell(address) => cells.get(address).cloned(),
+            Expr::Add(lhs, rhs) => Some(lhs.eval(inputs, cells)?.wrapping_add(rhs.eval(inputs, cells)?)),
+            Expr::Mul(lhs, rhs) => Some(lhs.eval(inputs, cells)?.wrapping_mul(rhs.eval(inputs, cells)?)),
+            Expr::LessThan(lhs, rhs) => Some((lhs.eval(inputs, cells)? < rhs.eval(inputs, cells)?) as i64),
+            Expr::Equals(lhs, rhs) => Some((lhs.eval(inputs, cells)? == rhs.eval(inputs, cells)?) as i64)
+        }
+    }
+
+    /// Replace the symbols that have values, folding whatever becomes constant.
+    pub fn substitute(&self, inputs: &BTreeMap<usize, i64>, cells: &BTreeMap<usize, i64>) -> Expr {
+        match self {
+            Expr::Const(_) => self.clone(),
+            Expr::Input(index) => inputs.get(index).map_or_else(|| self.clone(), |value| Expr::Const(*value)),
+            Expr::Cell(address) => cells.get(address).map_or_else(|| self.clone(), |value| Expr::Const(*value)),
+            Expr::Add(lhs, rhs) => Expr::sum(lhs.substitute(inputs, cells), rhs.substitute(inputs, cells)),
+            Expr::Mul(lhs, rhs) => Expr::product(lhs.substitute(inputs, cells), rhs.substitute(inputs, cells)),
+            Expr::LessThan(lhs, rhs) => Expr::less_than(lhs.substitute(inputs, cells), rhs.substitute(inputs, cells)),
+            Expr::Equals(lhs, rhs) => Expr::equals(lhs.substitute(inputs, cells), rhs.substitute(inputs, cells))
+        }
+    }
+
+    /// The inputs and cells the expression depends on.
+    pub fn symbols(&self) -> (BTreeSet<usize>, BTreeSet<usize>) {
+        let mut inputs = BTreeSet::new();
+        let mut cells = BTreeSet::new();
+
+        self.collect_symbols(&mut inputs, &mut cells);
+
+        (inputs, cells)
+    }
+
+    fn collect_symbols(&self, inputs: &mut BTreeSet<usize>, cells: &mut BTreeSet<usize>) {
+        match self {
+            Expr::Const(_) => {},
+            Expr::Input(index) => { inputs.insert(*index); },
+            Expr::Cell(address) => { cells.insert(*address); },
+            Expr::Add(lhs, rhs)|Expr::Mul(lhs, rhs)|Expr::LessThan(lhs, rhs)|Expr::Equals(lhs, rhs) => {
+                lhs.collect_symbols(inputs, cells);
+                rhs.collect_symbols(inputs, cells);
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expr::Const(value) => write!(f, "{}", value),
+            Expr::Input(index) => write!(f, "in{}", index),
+            Expr::Cell(address) => write!(f, "[{}]", address),
+            Expr::Add(lhs, rhs) => write!(f, "({} + {})", lhs, rhs),
+            Expr::Mul(lhs, rhs) => write!(f, "({} * {})", lhs, rhs),
+            Expr::LessThan(lhs, rhs) => write!(f, "({} < {})", lhs, rhs),
+            Expr::Equals(lhs, rhs) => write!(f, "({} == {})", lhs, rhs)
+        }
+    }
+}
+
+/// A branch decision a path depends on: `expr` was nonzero, or it was zero.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub struct Constraint {
+    pub expr: Expr,
+    pub nonzero: bool
+}
+
+impl Constraint {
+    /// Whether concrete values for the symbols satisfy the constraint, or None if some
+    /// are missing.
+    pub fn holds(&self, inputs: &[i64], cells: &BTreeMap<usize, i64>) -> Option<bool> {
+        self.expr.eval(inputs, cells).map(|value| (value != 0) == self.nonzero)
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} 0", self.expr, if self.nonzero { "!=" } else { "==" })
+    }
+}
+
+/// Why a path stopped.
+#[derive(PartialEq, Clone, Debug)]
+pub enum PathEnd {
+    Halted,
+    /// The program faulted for every assignment that reaches this point.
+    Fault(IntcodeError),
+    /// The path ran for the whole step limit.
+    StepLimit,
+    /// The path reached a symbolic branch after the path limit was used up.
+    PathLimit,
+    /// The path needs something that can't be tracked symbolically, such as an address,
+    /// jump target or instruction that depends on a symbol.
+    Unsupported { pc: usize, reason: String }
+}
+
+/// One way through the program.
+#[derive(PartialEq, Clone, Debug)]
+pub struct Path {
+    /// Every branch decision the path depends on, in the order they were made.
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Expr>,
+    /// How many inputs the path read.
+    pub inputs: usize,
+    pub end: PathEnd,
+    pc: usize,
+    relative_base: i64,
+    // The program image, then whatever has been written past it.
+    memory: Vec<Expr>,
+    beyond: BTreeMap<usize, Expr>,
+    steps: u64,
+    stopped: bool
+}
+
+impl Path {
+    /// What `address` holds at the end of the path.
+    pub fn cell(&self, address: usize) -> Expr {
+        match self.memory.get(address) {
+            Some(expr) => expr.clone(),
+            None => self.beyond.get(&address).cloned().unwrap_or(Expr::Const(0))
+        }
+    }
+
+    pub fn program_counter(&self) -> usize {
+        self.pc
+    }
+
+    /// Whether concrete values for the symbols take the program down this path, or None
+    /// if some are missing.
+    pub fn feasible(&self, inputs: &[i64], cells: &BTreeMap<usize, i64>) -> Option<bool> {
+        self.constraints.iter().try_fold(true, |all, constraint| Some(all && constraint.holds(inputs, cells)?))
+    }
+
+    fn stop(&mut self, end: PathEnd) {
+        self.end = end;
+        self.stopped = true;
+    }
+
+    fn unsupported(&mut self, reason: &str) {
+        self.stop(PathEnd::Unsupported { pc: self.pc, reason: reason.to_string() });
+    }
+
+    fn fault(&mut self, err: IntcodeError) {
+        self.stop(PathEnd::Fault(err));
+    }
+
+    fn write(&mut self, address: usize, value: Expr) {
+        match self.memory.get_mut(address) {
+            Some(cell) => *cell = value,
+            None => {
+                self.beyond.insert(address, value);
+            }
+        }
+    }
+
+    // A branch already decided on this path decides it again.
+    fn decided(&self, condition: &Expr) -> Option<bool> {
+        self.constraints.iter().find(|constraint| constraint.expr == *condition).map(|constraint| constraint.nonzero)
+    }
+}
+
+// A resolved operand: its value and, unless it's an immediate, the cell it came from.
+type Operand = (Expr, Option<usize>);
+
+/// Runs a program with some of its inputs and memory left unknown.
+///
+/// Every input is a fresh symbol, as is every cell passed to `make_symbolic`. Arithmetic
+/// and comparisons on symbols build up expressions; a jump whose condition depends on one
+/// forks the run into a path where the condition held and one where it didn't. Addresses,
+/// jump targets and instructions have to stay concrete, and a path that needs anything
+/// else ends as `PathEnd::Unsupported`.
+///
+/// Only the built-in opcodes are understood, and words are i64.
+pub struct SymbolicExecutor {
+    program: Vec<i64>,
+    symbolic: BTreeSet<usize>,
+    step_limit: u64,
+    path_limit: usize
+}
+
+impl SymbolicExecutor {
+    pub const DEFAULT_STEP_LIMIT : u64 = 100_000;
+    pub const DEFAULT_PATH_LIMIT : usize = 1024;
+
+    pub fn new(program: &[i64]) -> Self {
+        Self {
+            program: program.to_vec(),
+            symbolic: BTreeSet::new(),
+            step_limit: Self::DEFAULT_STEP_LIMIT,
+            path_limit: Self::DEFAULT_PATH_LIMIT
+        }
+    }
+
+    /// Treat the initial contents of `address` as unknown.
+    pub fn make_symbolic(&mut self, address: usize) {
+        self.symbolic.insert(address);
+    }
+
+    /// Instructions any one path may execute.
+    pub fn set_step_limit(&mut self, limit: u64) {
+        self.step_limit = limit;
+    }
+
+    /// Paths to explore. Once that many exist, a path stops at its next symbolic branch
+    /// instead of forking.
+    pub fn set_path_limit(&mut self, limit: usize) {
+        self.path_limit = limit;
+    }
+
+    fn initial_path(&self) -> Path {
+        let memory = self.program.iter().enumerate().map(|(address, word)| match self.symbolic.contains(&address) {
+            true => Expr::Cell(address),
+            false => Expr::Const(*word)
+        }).collect::<Vec<Expr>>();
+        let mut path = Path {
+            constraints: vec![], outputs: vec![], inputs: 0, end: PathEnd::Halted,
+            pc: 0, relative_base: 0, memory, beyond: BTreeMap::new(), steps: 0, stopped: false
+        };
+
+        for address in self.symbolic.iter().filter(|address| **address >= self.program.len()) {
+            path.write(*address, Expr::Cell(*address));
+        }
+
+        path
+    }
+
+    /// Explore every path through the program, depth first, taking the branch where the
+    /// condition held first.
+    pub fn explore(&self) -> Vec<Path> {
+        let mut pending = vec![self.initial_path()];
+        let mut finished = vec![];
+
+        let mut paths = 1;
+
+        while let Some(mut path) = pending.pop() {
+            while !path.stopped {
+                if path.steps >= self.step_limit {
+                    path.stop(PathEnd::StepLimit);
+                    break;
+                }
+
+                path.steps += 1;
+
+                if let Some(fork) = self.step(&mut path, paths < self.path_limit) {
+                    paths += 1;
+                    pending.push(fork);
+                }
+            }
+
+            finished.push(path);
+        }
+
+        finished
+    }
+
+    fn operand(&self, path: &mut Path, index: usize, mode: i64) -> Option<Operand> {
+        let word = path.cell(path.pc + 1 + index);
+        let address = match (mode, word.as_const()) {
+            (1, _) => return Some((word, None)),
+            (0, Some(address)) => address,
+            (2, Some(offset)) => path.relative_base.wrapping_add(offset),
+            (0, None)|(2, None) => {
+                path.unsupported("symbolic address");
+                return None;
+            },
+            _ => {
+                let instruction = path.cell(path.pc).as_const().unwrap_or(0);
+
+                path.fault(IntcodeError::InvalidParameterMode { pc: path.pc, instruction, mode });
+                return None;
+            }
+        };
+
+        if address < 0 {
+            path.fault(IntcodeError::NegativeAddress { pc: path.pc, address });
+            return None;
+        }
+
+        Some((path.cell(address as usize), Some(address as usize)))
+    }
+
+    // Execute one instruction, stopping the path if it ends. Returns the other side of a
+    // symbolic branch, if there was one.
+    fn step(&self, path: &mut Path, can_fork: bool) -> Option<Path> {
+        let (opcode, modes) = match path.cell(path.pc).as_const().map(Opcode::new) {
+            Some(Ok(decoded)) => decoded,
+            Some(Err(err)) => {
+                path.fault(err.at(path.pc));
+                return None;
+            },
+            None => {
+                path.unsupported("symbolic instruction");
+                return None;
+            }
+        };
+        let mut args = vec![];
+        let mut mode = modes;
+
+        for index in 0..opcode.get_size() - 1 {
+            args.push(self.operand(path, index, mode % 10)?);
+            mode /= 10;
+        }
+
+        let result = match opcode {
+            Opcode::Add => Expr::sum(args[0].0.clone(), args[1].0.clone()),
+            Opcode::Mult => Expr::product(args[0].0.clone(), args[1].0.clone()),
+            Opcode::LessThan => Expr::less_than(args[0].0.clone(), args[1].0.clone()),
+            Opcode::Equals => Expr::equals(args[0].0.clone(), args[1].0.clone()),
+            Opcode::Input => {
+                path.inputs += 1;
+                Expr::Input(path.inputs - 1)
+            },
+            Opcode::Output => {
+                path.outputs.push(args[0].0.clone());
+                path.pc += 2;
+                return None;
+            },
+            Opcode::RBO => {
+                match args[0].0.as_const() {
+                    Some(adjustment) => {
+                        path.relative_base = path.relative_base.wrapping_add(adjustment);
+                        path.pc += 2;
+                    },
+                    None => path.unsupported("symbolic relative base")
+                }
+
+                return None;
+            },
+            Opcode::JumpIfTrue|Opcode::JumpIfFalse => return Self::branch(path, opcode == Opcode::JumpIfTrue, &args, can_fork),
+            Opcode::ProgramEnd => {
+                path.stop(PathEnd::Halted);
+                return None;
+            },
+            Opcode::Custom(_) => {
+                path.unsupported("custom opcode");
+                return None;
+            }
+        };
+
+        match args[opcode.write_operand().unwrap()].1 {
+            Some(address) => {
+                path.write(address, result);
+                path.pc += opcode.get_size();
+            },
+            None => {
+                let instruction = path.cell(path.pc).as_const().unwrap_or(0);
+
+                path.fault(IntcodeError::WriteInImmediateMode { pc: path.pc, instruction });
+            }
+        }
+
+        None
+    }
+
+    fn branch(path: &mut Path, when: bool, args: &[Operand], can_fork: bool) -> Option<Path> {
+        let condition = args[0].0.clone();
+        let decided = condition.as_const().map(|value| value != 0).or_else(|| path.decided(&condition));
+        let fork = match decided {
+            Some(_) => None,
+            None if !can_fork => {
+                path.stop(PathEnd::PathLimit);
+                return None;
+            },
+            None => {
+                let mut other = path.clone();
+
+                other.constraints.push(Constraint { expr: condition.clone(), nonzero: false });
+                path.constraints.push(Constraint { expr: condition, nonzero: true });
+                Self::jump(&mut other, !when, &args[1]);
+
+                Some(other)
+            }
+        };
+
+        Self::jump(path, decided.unwrap_or(true) == when, &args[1]);
+
+        fork
+    }
+
+    fn jump(path: &mut Path, taken: bool, target: &Operand) {
+        if !taken {
+            path.pc += 3;
+            return;
+        }
+
+        match target.0.as_const() {
+            Some(address) if address >= 0 => path.pc = address as usize,
+            Some(address) => path.fault(IntcodeError::NegativeAddress { pc: path.pc, address }),
+            None => path.unsupported("symbolic jump target")
+        }
+    }
+}
+
+#[cfg(test)]
+mod symbolic_tests {
+    use crate::int_code_computer::symbolic::*;
+    use crate::int_code_computer::IntCodeMachine;
+
+    // Outputs 1 if the input is below 10 and 2 otherwise, then does the same check again.
+    const BELOW_TEN : [i64; 23] = [3,21,1007,21,10,22,1005,22,14,104,2,1105,1,16,104,1,1005,22,20,99,99,0,0];
+
+    fn display(exprs: &[Expr]) -> Vec<String> {
+        exprs.iter().map(|expr| expr.to_string()).collect()
+    }
+
+    #[test]
+    fn patched_cells_become_expressions(){
+        // [0] = ([9] + [10]) * 3, the shape of day 2's programs.
+        let program = [1,9,10,0,1002,0,3,0,99,0,0];
+        let mut executor = SymbolicExecutor::new(&program);
+
+        executor.make_symbolic(9);
+        executor.make_symbolic(10);
+
+        let paths = executor.explore();
+
+        assert_eq!(paths.len(), 1);
+        assert_eq!(paths[0].end, PathEnd::Halted);
+        assert_eq!(paths[0].cell(0).to_string(), "(([9] + [10]) * 3)");
+
+        let cells = vec![(9, 12), (10, 2)].into_iter().collect();
+        let mut patched = program.to_vec();
+
+        patched[9] = 12;
+        patched[10] = 2;
+        IntCodeMachine::run_program(&mut patched, None).unwrap();
+
+        assert_eq!(paths[0].cell(0).eval(&[], &cells), Some(patched[0]));
+    }
+
+    #[test]
+    fn forks_on_symbolic_branches(){
+        let paths = SymbolicExecutor::new(&BELOW_TEN).explore();
+
+        // The second check is already decided, so there are only two paths.
+        assert_eq!(paths.len(), 2);
+        assert!(paths.iter().all(|path| path.end == PathEnd::Halted && path.inputs == 1));
+        assert_eq!(paths[0].constraints.iter().map(|c| c.to_string()).collect::<Vec<String>>(), vec!["(in0 < 10) != 0"]);
+        assert_eq!(display(&paths[0].outputs), vec!["1"]);
+        assert_eq!(paths[1].constraints[0].to_string(), "(in0 < 10) == 0");
+        assert_eq!(display(&paths[1].outputs), vec!["2"]);
+
+        for (input, path) in [(3, 0), (10, 1)].iter() {
+            let cells = BTreeMap::new();
+
+            assert_eq!(paths[*path].feasible(&[*input], &cells), Some(true));
+            assert_eq!(paths[1 - *path].feasible(&[*input], &cells), Some(false));
+        }
+    }
+
+    #[test]
+    fn outputs_carry_expressions(){
+        // Reads two values and outputs their sum and whether they're equal.
+        let paths = SymbolicExecutor::new(&[3,17,3,18,1,17,18,19,8,17,18,20,4,19,4,20,99,0,0,0,0]).explore();
+
+        assert_eq!(paths.len(), 1);
+        assert_eq!(display(&paths[0].outputs), vec!["(in0 + in1)", "(in0 == in1)"]);
+    }
+
+    #[test]
+    fn limits_stop_unbounded_forking(){
+        // Counts the input down to zero.
+        let mut executor = SymbolicExecutor::new(&[3,10,1001,10,-1,10,1005,10,2,99,0]);
+
+        executor.set_path_limit(4);
+
+        let paths = executor.explore();
+
+        assert_eq!(paths.len(), 4);
+        assert_eq!(paths[0].end, PathEnd::PathLimit);
+        assert_eq!(paths[0].cell(10).to_string(), "(in0 + -4)");
+        assert!(paths[1..].iter().all(|path| path.end == PathEnd::Halted));
+        assert_eq!(paths[3].constraints.iter().map(|c| c.to_string()).collect::<Vec<String>>(), vec!["(in0 + -1) == 0"]);
+
+        let mut executor = SymbolicExecutor::new(&[1105,1,0]);
+
+        executor.set_step_limit(50);
+
+        assert_eq!(executor.explore()[0].end, PathEnd::StepLimit);
+    }
+
+    #[test]
+    fn symbolic_addresses_are_unsupported(){
+        // The input becomes the operand of the OUTPUT that follows.
+        let paths = SymbolicExecutor::new(&[3,3,4,0,99]).explore();
+
+        assert_eq!(paths[0].end, PathEnd::Unsupported { pc : 2, reason : "symbolic address".to_string() });
+
+        let paths = SymbolicExecutor::new(&[3,0,98]).explore();
+
+        assert_eq!(paths[0].end, PathEnd::Fault(IntcodeError::InvalidOpcode { pc : 2, instruction : 98 }));
+    }
+
+    #[test]
+    fn writes_far_past_the_program(){
+        let paths = SymbolicExecutor::new(&[1101,1,1,4_000_000_000,99]).explore();
+
+        assert_eq!(paths[0].end, PathEnd::Halted);
+        assert_eq!(paths[0].cell(4_000_000_000), Expr::Const(2));
+        assert_eq!(paths[0].cell(5), Expr::Const(0));
+    }
+}
@@ -0,0 +1,217 @@
+use std::convert::TryFrom;
+use std::fmt::{Debug, Display};
+use std::hash::Hash;
+use std::str::FromStr;
+use num_bigint::{BigInt, Sign};
+use num_traits::{ToPrimitive, Zero};
+use crate::int_code_computer::arithmetic::Arithmetic;
+
+/// A machine word: what memory cells, operands, inputs and outputs hold.
+///
+/// Addresses, parameter modes and the relative base stay plain integers; a word only
+/// has to convert into them when it is used as one. Errors carry words as i64,
+/// saturated if they don't fit.
+pub trait Word: Clone + Eq + Ord + Hash + Debug + Display + FromStr<Err: Display> + 'static {
+    /// Only called with small constants (0, 1, comparison results), which every word
+    /// type can represent.
+    fn from_i64(value: i64) -> Self;
+
+    fn to_i64(&self) -> Option<i64>;
+
+    fn is_zero(&self) -> bool;
+
+    /// None only under `Arithmetic::Checked`, when the result doesn't fit.
+    fn add(&self, other: &Self, arithmetic: Arithmetic) -> Option<Self>;
+
+    /// None only under `Arithmetic::Checked`, when the result doesn't fit.
+    fn mul(&self, other: &Self, arithmetic: Arithmetic) -> Option<Self>;
+
+    /// Bits mixed into memory fingerprints. Zero must map to 0.
+    fn hash_bits(&self) -> u64;
+
+    /// Little-endian two's complement, without redundant sign bytes.
+    fn to_signed_bytes(&self) -> Vec<u8>;
+
+    /// Inverse of `to_signed_bytes`, also accepting redundant sign bytes. None if the
+    /// value doesn't fit.
+    fn from_signed_bytes(bytes: &[u8]) -> Option<Self>;
+
+    fn saturating_i64(&self) -> i64 {
+        match self.to_i64() {
+            Some(value) => value,
+            None if *self < Self::from_i64(0) => i64::MIN,
+            None => i64::MAX
+        }
+    }
+}
+
+macro_rules! primitive_word {
+    ($word:ty) => {
+        impl Word for $word {
+            fn from_i64(value: i64) -> Self {
+                value as $word
+            }
+
+            fn to_i64(&self) -> Option<i64> {
+                i64::try_from(*self).ok()
+            }
+
+            fn is_zero(&self) -> bool {
+                *self == 0
+            }
+
+            fn add(&self, other: &Self, arithmetic: Arithmetic) -> Option<Self> {
+                match arithmetic {
+                    Arithmetic::Wrapping => Some(self.wrapping_add(*other)),
+                    Arithmetic::Checked => self.checked_add(*other),
+                    Arithmetic::Saturating => Some(self.saturating_add(*other))
+                }
+            }
+
+            fn mul(&self, other: &Self, arithmetic: Arithmetic) -> Option<Self> {
+                match arithmetic {
+                    Arithmetic::Wrapping => Some(self.wrapping_mul(*other)),
+                    Arithmetic::Checked => self.checked_mul(*other),
+                    Arithmetic::Saturating => Some(self.saturating_mul(*other))
+                }
+            }
+
+            fn hash_bits(&self) -> u64 {
+                let wide = *self as i128;
+
+                (wide as u64) ^ ((wide >> 64) as u64)
+            }
+
+            fn to_signed_bytes(&self) -> Vec<u8> {
+                let mut bytes = self.to_le_bytes().to_vec();
+
+                while bytes.len() > 1 {
+                    let (last, next) = (bytes[bytes.len() - 1], bytes[bytes.len() - 2]);
+
+                    match (last, next & 0x80) {
+                        (0, 0)|(0xff, 0x80) => bytes.pop(),
+                        _ => break
+                    };
+                }
+
+                bytes
+            }
+
+            fn from_signed_bytes(bytes: &[u8]) -> Option<Self> {
+                const SIZE : usize = std::mem::size_of::<$word>();
+
+                let fill = match bytes.last() {
+                    Some(last) if last & 0x80 != 0 => 0xff,
+                    _ => 0
+                };
+                let mut buffer = [fill; SIZE];
+                let kept = bytes.len().min(SIZE);
+
+                buffer[..kept].copy_from_slice(&bytes[..kept]);
+
+                // Anything past the word's width has to be sign extension.
+                let extended = bytes[kept..].iter().all(|byte| *byte == fill) && (buffer[SIZE - 1] & 0x80 != 0) == (fill != 0);
+
+                match extended {
+                    true => Some(<$word>::from_le_bytes(buffer)),
+                    false => None
+                }
+            }
+        }
+    };
+}
+
+primitive_word!(i32);
+primitive_word!(i64);
+primitive_word!(i128);
+
+/// Arbitrary precision: never overflows, so the arithmetic policy doesn't matter.
+impl Word for BigInt {
+    fn from_i64(value: i64) -> Self {
+        BigInt::from(value)
+    }
+
+    fn to_i64(&self) -> Option<i64> {
+        ToPrimitive::to_i64(self)
+    }
+
+    fn is_zero(&self) -> bool {
+        Zero::is_zero(self)
+    }
+
+    fn add(&self, other: &Self, _: Arithmetic) -> Option<Self> {
+        Some(self + other)
+    }
+
+    fn mul(&self, other: &Self, _: Arithmetic) -> Option<Self> {
+        Some(self * other)
+    }
+
+    fn hash_bits(&self) -> u64 {
+        let (sign, digits) = self.to_u64_digits();
+        let bits = digits.iter().enumerate().fold(0, |bits, (index, digit)| bits ^ digit.rotate_left(index as u32 * 7));
+
+        match sign {
+            Sign::Minus => !bits,
+            _ => bits
+        }
+    }
+
+    fn to_signed_bytes(&self) -> Vec<u8> {
+        self.to_signed_bytes_le()
+    }
+
+    fn from_signed_bytes(bytes: &[u8]) -> Option<Self> {
+        Some(BigInt::from_signed_bytes_le(bytes))
+    }
+}
+
+#[cfg(test)]
+mod word_tests {
+    use crate::int_code_computer::word::*;
+    use crate::int_code_computer::{IntCodeMachine, MachineState};
+    use crate::int_code_computer::fixtures::SQUARE;
+
+    fn square<W: Word>(value: W) -> (Result<MachineState, crate::int_code_computer::IntcodeError>, Vec<W>) {
+        let program : Vec<W> = SQUARE.iter().map(|word| W::from_i64(*word)).collect();
+        let mut machine = IntCodeMachine::new(&program, Some(&vec![value]));
+
+        machine.set_arithmetic(Arithmetic::Checked);
+
+        (machine.run(), machine.output)
+    }
+
+    #[test]
+    fn word_widths(){
+        assert_eq!(square(46_340i32).1, vec![2_147_395_600]);
+        assert!(square(46_341i32).0.is_err());
+        assert!(square(1i64 << 32).0.is_err());
+        assert_eq!(square(1i128 << 32).1, vec![1i128 << 64]);
+        assert_eq!(square(BigInt::from(1) << 100).1, vec![BigInt::from(1) << 200]);
+    }
+
+    #[test]
+    fn saturates_for_errors(){
+        let big : BigInt = BigInt::from(1) << 80;
+
+        assert_eq!(big.saturating_i64(), i64::MAX);
+        assert_eq!((-(1i128 << 80)).saturating_i64(), i64::MIN);
+        assert_eq!(7i32.saturating_i64(), 7);
+    }
+
+    #[test]
+    fn hash_bits_of_zero(){
+        assert_eq!(0i32.hash_bits(), 0);
+        assert_eq!(BigInt::from(0).hash_bits(), 0);
+        assert_ne!(BigInt::from(-1).hash_bits(), BigInt::from(1).hash_bits());
+    }
+
+    #[test]
+    fn runs_day_9_with_big_words(){
+        let program : Vec<BigInt> = IntCodeMachine::read_file_into_program("day-9-part-1-input").unwrap();
+        let mut machine = IntCodeMachine::new(&program, Some(&vec![BigInt::from(1)]));
+
+        assert_eq!(machine.run(), Ok(MachineState::Halted));
+        assert_eq!(machine.output, vec![BigInt::from(2494485073i64)]);
+    }
+}
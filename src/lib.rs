@@ -1,8 +1,14 @@
 use std::fs;
+use std::io;
+use std::path::PathBuf;
 
 pub mod int_code_computer;
 
-pub fn read_input_file(name : &str) -> String{
-    // panic if not found...
-    return fs::read_to_string(format!("resources/{}", name)).unwrap()
-}
\ No newline at end of file
+/// Where the puzzle input called `name` lives.
+pub fn resource_path(name : &str) -> PathBuf {
+    PathBuf::from("resources").join(name)
+}
+
+pub fn read_input_file(name : &str) -> io::Result<String>{
+    fs::read_to_string(resource_path(name))
+}